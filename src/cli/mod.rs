@@ -0,0 +1,448 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use image::{DynamicImage, ImageFormat};
+
+#[cfg(unix)]
+use crate::ipc;
+use crate::{
+    reference::{board, color, crop, frame, import, similar, Image, Library, Storage},
+    renderer::{Renderer, Vision},
+};
+
+#[derive(Parser)]
+#[command(
+    name = "rustyref",
+    about = "Lightweight reference board",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    /// Images, folders of images or boards to open
+    pub paths: Vec<PathBuf>,
+
+    /// Import the images written to this folder while the board is open
+    #[arg(long = "watch", value_name = "FOLDER")]
+    pub watch_folders: Vec<PathBuf>,
+
+    /// Open a new window even if RustyRef is already running
+    #[arg(long)]
+    pub new_window: bool,
+
+    /// Say so when an imported image is already on the board
+    #[arg(long)]
+    pub warn_duplicates: bool,
+
+    /// Crop the uniform or transparent borders of imported images, with an
+    /// optional tolerance out of 255
+    #[arg(long, value_name = "TOLERANCE", num_args = 0..=1, default_missing_value = "8")]
+    pub trim: Option<u8>,
+
+    /// Differing bits, out of 64, under which F and Shift+F count two images
+    /// as similar
+    #[arg(long, value_name = "BITS", default_value_t = similar::DEFAULT_THRESHOLD)]
+    pub similarity: u32,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Options given for the window that a running instance does not take
+    /// over IPC, it imports with its own.
+    pub fn window_only_options(&self) -> Vec<&'static str> {
+        let mut options = Vec::new();
+        if self.warn_duplicates {
+            options.push("--warn-duplicates");
+        }
+        if self.trim.is_some() {
+            options.push("--trim");
+        }
+        if self.similarity != similar::DEFAULT_THRESHOLD {
+            options.push("--similarity");
+        }
+        options
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List the images of a board
+    Info(InfoArgs),
+    /// Render a board, or a region of it, to a PNG, JPEG or WebP file
+    Export(ExportArgs),
+    /// Rewrite a board with the pixels of every image embedded in it
+    Pack(PackArgs),
+    /// Append images or folders of images to a board, creating it if needed
+    Add(AddArgs),
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Board file to inspect
+    board: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Board file to render
+    board: PathBuf,
+    /// Output image, the format is taken from the extension
+    output: PathBuf,
+    /// Region of the board to render as `x,y,width,height`, the whole board by default
+    #[arg(long, value_parser = parse_region)]
+    region: Option<[f32; 4]>,
+    /// Render the frame of this name only
+    #[arg(long, conflicts_with = "region")]
+    frame: Option<String>,
+    /// Width of the output in pixels
+    #[arg(long)]
+    width: Option<u32>,
+    /// Height of the output in pixels
+    #[arg(long)]
+    height: Option<u32>,
+    /// Simulate protanopia, deuteranopia, tritanopia or achromatopsia
+    #[arg(long, value_parser = parse_vision)]
+    vision: Option<Vision>,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// Board file to pack
+    board: PathBuf,
+    /// Where to write the packed board, the board itself by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct AddArgs {
+    /// Board file to append to
+    board: PathBuf,
+    /// Images or folders of images to append
+    #[arg(required = true)]
+    images: Vec<PathBuf>,
+    /// Link to the files instead of embedding their pixels in the board
+    #[arg(long)]
+    link: bool,
+    /// Crop the uniform or transparent borders of the images, with an
+    /// optional tolerance out of 255
+    #[arg(long, value_name = "TOLERANCE", num_args = 0..=1, default_missing_value = "8")]
+    trim: Option<u8>,
+}
+
+fn parse_vision(value: &str) -> Result<Vision, String> {
+    Vision::find(value).ok_or_else(|| {
+        let names: Vec<String> = Vision::ALL.iter().map(Vision::to_string).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn parse_region(value: &str) -> Result<[f32; 4], String> {
+    let parts = value
+        .split(',')
+        .map(|part| part.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match parts[..] {
+        [x, y, width, height] if width > 0. && height > 0. => Ok([x, y, width, height]),
+        [_, _, _, _] => Err("region width and height must be positive".to_string()),
+        _ => Err("expected x,y,width,height".to_string()),
+    }
+}
+
+/// Output size for `region`: the requested dimensions, the missing one
+/// following the region aspect ratio, or one pixel per board unit.
+fn output_size(region: [f32; 4], width: Option<u32>, height: Option<u32>, max: u32) -> (u32, u32) {
+    let aspect = region[2] / region[3];
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width as f32, height as f32),
+        (Some(width), None) => (width as f32, width as f32 / aspect),
+        (None, Some(height)) => (height as f32 * aspect, height as f32),
+        (None, None) => (region[2], region[3]),
+    };
+
+    let shrink = (max as f32 / width.max(height)).min(1.);
+    (
+        ((width * shrink).round() as u32).max(1),
+        ((height * shrink).round() as u32).max(1),
+    )
+}
+
+pub fn export(args: ExportArgs) -> Result<(), Box<dyn Error>> {
+    let mut library = board::load(&args.board)?;
+    let mut region = args.region;
+    if let Some(name) = &args.frame {
+        let frame_id = frame::find(&library, name).ok_or_else(|| format!("no frame {}", name))?;
+        let frame = library.get_frame_mut(&frame_id).ok_or("no frame")?;
+        frame.collapsed = false;
+        region = Some([
+            frame.position[0],
+            frame.position[1],
+            frame.size[0],
+            frame.size[1],
+        ]);
+    }
+    let region = region
+        .or_else(|| library.bounds())
+        .ok_or("the board is empty")?;
+
+    let mut renderer =
+        pollster::block_on(Renderer::headless()).ok_or("no graphics adapter available")?;
+    let (width, height) = output_size(region, args.width, args.height, renderer.max_render_size());
+
+    renderer.set_library(library);
+    if let Some(vision) = args.vision {
+        renderer.set_vision(vision);
+    }
+    let render = DynamicImage::ImageRgba8(renderer.render_region(region, width, height));
+
+    let format = ImageFormat::from_path(&args.output)?;
+    match format {
+        // JPEG has no alpha channel.
+        ImageFormat::Jpeg => {
+            DynamicImage::ImageRgb8(render.to_rgb8()).save_with_format(&args.output, format)?
+        }
+        _ => render.save_with_format(&args.output, format)?,
+    }
+
+    println!("Exported {}x{} to {}", width, height, args.output.display());
+    Ok(())
+}
+
+/// One line listing of an image: id, position on the board, size, storage
+/// and file.
+fn image_line(library: &Library, id: &uuid::Uuid, image: &Image) -> String {
+    let [x, y] = library.world(id).apply(image.position).map(f32::round);
+    let [width, height] = image.size();
+    let source = image
+        .source
+        .as_ref()
+        .map(|source| source.path.display().to_string())
+        .unwrap_or_default();
+    let crop = match image.crop {
+        Some([x, y, width, height]) => format!("  crop {},{} {}x{}", x, y, width, height),
+        None => String::new(),
+    };
+    let palette: String = image
+        .palette
+        .iter()
+        .map(|color| format!(" {}", color::hex(*color)))
+        .collect();
+    let palette = if palette.is_empty() {
+        palette
+    } else {
+        format!("  palette{}", palette)
+    };
+    let flags = match (image.locked, image.hidden) {
+        (true, true) => "  locked hidden",
+        (true, false) => "  locked",
+        (false, true) => "  hidden",
+        (false, false) => "",
+    };
+    format!(
+        "{}  at {},{}  {}x{}  {}  {}{}{}{}",
+        id,
+        x,
+        y,
+        width,
+        height,
+        image.storage_label(),
+        source,
+        crop,
+        palette,
+        flags
+    )
+}
+
+pub fn info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
+    let library = board::load(&args.board)?;
+
+    let mut images: Vec<_> = library.iter().collect();
+    images.sort_by(|(_, a), (_, b)| {
+        a.position[1]
+            .total_cmp(&b.position[1])
+            .then(a.position[0].total_cmp(&b.position[0]))
+    });
+    for (id, image) in images {
+        println!("{}", image_line(&library, id, image));
+    }
+
+    let mut texts: Vec<_> = library.texts().collect();
+    texts.sort_by(|(_, a), (_, b)| {
+        a.position[1]
+            .total_cmp(&b.position[1])
+            .then(a.position[0].total_cmp(&b.position[0]))
+    });
+    for (id, text) in &texts {
+        let [x, y] = library.world(id).apply(text.position).map(f32::round);
+        let [width, height] = text.size();
+        println!(
+            "{}  at {},{}  {}x{}  text  {}",
+            id,
+            x,
+            y,
+            width.round(),
+            height.round(),
+            text.content.lines().next().unwrap_or_default()
+        );
+    }
+
+    let mut chips: Vec<_> = library.chips().collect();
+    chips.sort_by(|(_, a), (_, b)| {
+        a.position[1]
+            .total_cmp(&b.position[1])
+            .then(a.position[0].total_cmp(&b.position[0]))
+    });
+    for (id, chip) in &chips {
+        let [x, y] = library.world(id).apply(chip.position).map(f32::round);
+        let [width, height] = chip.size();
+        println!(
+            "{}  at {},{}  {}x{}  chip  {}  {}",
+            id,
+            x,
+            y,
+            width.round(),
+            height.round(),
+            color::hex(chip.color),
+            chip.name
+        );
+    }
+
+    let mut annotations: Vec<_> = library
+        .annotations()
+        .map(|(id, annotation)| (id, annotation, library.rect(id).unwrap_or_default()))
+        .collect();
+    annotations.sort_by(|(_, _, (a, _)), (_, _, (b, _))| {
+        a[1].total_cmp(&b[1]).then(a[0].total_cmp(&b[0]))
+    });
+    for (id, annotation, (position, [width, height])) in &annotations {
+        let attached = annotation
+            .attached_to
+            .map(|image_id| format!("on {}", image_id))
+            .unwrap_or_default();
+        println!(
+            "{}  at {},{}  {}x{}  {}  {}",
+            id,
+            position[0].round(),
+            position[1].round(),
+            width.round(),
+            height.round(),
+            annotation.shape.name(),
+            attached
+        );
+    }
+
+    match library.bounds() {
+        Some([x, y, width, height]) => println!(
+            "{} images, {} texts, {} chips, {} annotations, {} groups, bounds {},{} {}x{}",
+            library.len(),
+            texts.len(),
+            chips.len(),
+            annotations.len(),
+            library.groups().count(),
+            x,
+            y,
+            width,
+            height
+        ),
+        None => println!("empty board"),
+    }
+    if !library.swatches.is_empty() {
+        let swatches: Vec<String> = library.swatches.iter().map(|c| color::hex(*c)).collect();
+        println!("swatches {}", swatches.join(" "));
+    }
+    Ok(())
+}
+
+pub fn pack(args: PackArgs) -> Result<(), Box<dyn Error>> {
+    let mut library = board::load(&args.board)?;
+    let keys: Vec<uuid::Uuid> = library.keys().copied().collect();
+    let mut packed = 0;
+    for key in keys {
+        let Some(image) = library.get_mut(&key) else {
+            continue;
+        };
+        match (image.storage, image.missing) {
+            (Storage::Linked, None) => {
+                image.storage = Storage::Embedded;
+                packed += 1;
+            }
+            (Storage::Linked, Some(_)) => eprintln!(
+                "rustyref: {} is missing, left linked",
+                image.source.as_ref().map_or_else(
+                    || key.to_string(),
+                    |source| source.path.display().to_string()
+                )
+            ),
+            (Storage::Embedded, _) => {}
+        }
+    }
+
+    let output = args.output.unwrap_or(args.board);
+    board::save(&library, &output)?;
+
+    println!("Packed {} images into {}", packed, output.display());
+    Ok(())
+}
+
+pub fn add(args: AddArgs) -> Result<(), Box<dyn Error>> {
+    let mut library = if args.board.exists() {
+        board::load(&args.board)?
+    } else {
+        Library::new()
+    };
+
+    let mut added = 0;
+    for path in import::expand_paths(&args.images)? {
+        let mut image = Image::open(&path, library.next_position())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if args.link {
+            image.storage = Storage::Linked;
+        }
+        if let Some(tolerance) = args.trim {
+            image.crop = crop::content(&image.image, tolerance);
+        }
+        library.insert(image);
+        added += 1;
+    }
+    board::save(&library, &args.board)?;
+
+    println!("Added {} images to {}", added, args.board.display());
+    Ok(())
+}
+
+/// Hands `paths` over to the window that is already running.
+#[cfg(unix)]
+/// Sends `paths` and `watch_folders` to the running instance. The options in
+/// `ignored` cannot go with them, the user is told so.
+pub fn forward(
+    paths: Vec<PathBuf>,
+    watch_folders: Vec<PathBuf>,
+    ignored: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    let mut stream = ipc::connect().ok_or("RustyRef is not running")?;
+    if !ignored.is_empty() {
+        eprintln!(
+            "rustyref: {} ignored, RustyRef is already running, use --new-window to open with them",
+            ignored.join(" and ")
+        );
+    }
+
+    let mut commands = Vec::new();
+    for path in import::expand_paths(&paths)? {
+        if import::is_board_path(&path) {
+            commands.push(ipc::Command::LoadBoard(path));
+        } else {
+            commands.push(ipc::Command::AddImageFromPath(path));
+        }
+    }
+    commands.extend(watch_folders.into_iter().map(ipc::Command::WatchFolder));
+    commands.push(ipc::Command::Focus);
+
+    for command in &commands {
+        if let Err(e) = ipc::send(&mut stream, command)? {
+            eprintln!("rustyref: {}", e);
+        }
+    }
+    Ok(())
+}
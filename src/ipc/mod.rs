@@ -0,0 +1,189 @@
+//! Local socket the running window listens on, so other tools can push
+//! images to the open board instead of starting a new window.
+//!
+//! The protocol is line based, one command per line:
+//!
+//! ```text
+//! add-image-from-path <path>
+//! add-image-from-bytes <length>    followed by <length> bytes of image data
+//! focus
+//! load-board <path>
+//! watch-folder <path>
+//! ```
+//!
+//! Every command is answered with `ok` or `error <message>` on its own line.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::ui::UserEvent;
+
+/// Largest image accepted by `add-image-from-bytes`.
+const MAX_IMAGE_BYTES: usize = 256 * 1024 * 1024;
+
+pub enum Command {
+    AddImageFromPath(PathBuf),
+    AddImageFromBytes(Vec<u8>),
+    Focus,
+    LoadBoard(PathBuf),
+    WatchFolder(PathBuf),
+}
+
+/// A command waiting to be run on the event loop, the client is answered once
+/// `reply` is called.
+pub struct Request {
+    pub command: Command,
+    reply: mpsc::Sender<Result<(), String>>,
+}
+
+impl Request {
+    pub fn reply(self, result: Result<(), String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("rustyref.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("rustyref-{}.sock", user))
+        }
+    }
+}
+
+/// Starts accepting clients in the background, every request is forwarded to
+/// the event loop through `proxy`.
+pub fn listen(proxy: EventLoopProxy<UserEvent>) -> io::Result<()> {
+    let path = socket_path();
+    let listener = match UnixListener::bind(&path) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(e);
+            }
+            // Left behind by an instance that did not shut down cleanly.
+            fs::remove_file(&path)?;
+            UnixListener::bind(&path)?
+        }
+        result => result?,
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let proxy = proxy.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(stream, proxy) {
+                    log::warn!("IPC client error: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// Removes the socket file, called when the window that bound it closes.
+pub fn shutdown() {
+    let _ = fs::remove_file(socket_path());
+}
+
+fn serve(stream: UnixStream, proxy: EventLoopProxy<UserEvent>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let result = match read_command(line.trim_end(), &mut reader) {
+            Ok(command) => dispatch(command, &proxy),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => writeln!(writer, "ok")?,
+            Err(e) => writeln!(writer, "error {}", e)?,
+        }
+        line.clear();
+    }
+
+    Ok(())
+}
+
+fn read_command(line: &str, reader: &mut impl Read) -> Result<Command, String> {
+    let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+    match name {
+        "add-image-from-path" if !argument.is_empty() => {
+            Ok(Command::AddImageFromPath(argument.into()))
+        }
+        "add-image-from-bytes" => {
+            let length: usize = argument
+                .parse()
+                .map_err(|_| "expected the image length in bytes".to_string())?;
+            if length > MAX_IMAGE_BYTES {
+                return Err(format!("images are limited to {} bytes", MAX_IMAGE_BYTES));
+            }
+            let mut bytes = vec![0; length];
+            reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+            Ok(Command::AddImageFromBytes(bytes))
+        }
+        "focus" => Ok(Command::Focus),
+        "load-board" if !argument.is_empty() => Ok(Command::LoadBoard(argument.into())),
+        "watch-folder" if !argument.is_empty() => Ok(Command::WatchFolder(argument.into())),
+        _ => Err(format!("unknown command '{}'", line)),
+    }
+}
+
+fn dispatch(command: Command, proxy: &EventLoopProxy<UserEvent>) -> Result<(), String> {
+    let (reply, result) = mpsc::channel();
+    proxy
+        .send_event(UserEvent::Ipc(Request { command, reply }))
+        .map_err(|_| "the window is closing".to_string())?;
+    result
+        .recv()
+        .unwrap_or_else(|_| Err("the window is closing".to_string()))
+}
+
+/// Client side: connects to the running window, if any.
+pub fn connect() -> Option<UnixStream> {
+    UnixStream::connect(socket_path()).ok()
+}
+
+/// Sends `command` over `stream` and waits for the answer.
+pub fn send(stream: &mut UnixStream, command: &Command) -> io::Result<Result<(), String>> {
+    match command {
+        Command::AddImageFromPath(path) => {
+            writeln!(stream, "add-image-from-path {}", absolute(path).display())?
+        }
+        Command::AddImageFromBytes(bytes) => {
+            writeln!(stream, "add-image-from-bytes {}", bytes.len())?;
+            stream.write_all(bytes)?;
+        }
+        Command::Focus => writeln!(stream, "focus")?,
+        Command::LoadBoard(path) => writeln!(stream, "load-board {}", absolute(path).display())?,
+        Command::WatchFolder(path) => {
+            writeln!(stream, "watch-folder {}", absolute(path).display())?
+        }
+    }
+
+    let mut answer = String::new();
+    BufReader::new(&*stream).read_line(&mut answer)?;
+    match answer.trim_end().split_once(' ') {
+        Some(("error", message)) => Ok(Err(message.to_string())),
+        _ if answer.trim_end() == "ok" => Ok(Ok(())),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected answer '{}'", answer.trim_end()),
+        )),
+    }
+}
+
+/// The running window does not share our working directory.
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
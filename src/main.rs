@@ -1,11 +1,44 @@
-use ui::run;
+use clap::Parser;
+use cli::{Cli, Command};
+use ui::{run, Options};
 
+pub mod cli;
+#[cfg(unix)]
+pub mod ipc;
 pub mod reference;
 pub mod renderer;
 pub mod ui;
+pub mod watch;
 
 fn main() {
     env_logger::init();
 
-    pollster::block_on(run());
+    let cli = Cli::parse();
+    #[cfg(unix)]
+    let window_only_options = cli.window_only_options();
+    let result = match cli.command {
+        Some(Command::Info(args)) => cli::info(args),
+        Some(Command::Export(args)) => cli::export(args),
+        Some(Command::Pack(args)) => cli::pack(args),
+        Some(Command::Add(args)) => cli::add(args),
+        #[cfg(unix)]
+        None if !cli.new_window && ipc::connect().is_some() => {
+            cli::forward(cli.paths, cli.watch_folders, &window_only_options)
+        }
+        None => {
+            pollster::block_on(run(Options {
+                paths: cli.paths,
+                watch_folders: cli.watch_folders,
+                warn_duplicates: cli.warn_duplicates,
+                trim: cli.trim,
+                similarity: cli.similarity,
+            }));
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("rustyref: {}", e);
+        std::process::exit(1);
+    }
 }
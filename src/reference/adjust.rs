@@ -0,0 +1,189 @@
+//! Color adjustments of an image, applied when it is drawn so the pixels
+//! themselves never change.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Adjustments {
+    /// Added to every channel, from -1 to 1.
+    pub brightness: f32,
+    /// Spread of the channels around mid gray, 1 leaves them.
+    pub contrast: f32,
+    /// 0 is gray, 1 leaves the colors.
+    pub saturation: f32,
+    /// Turn of the hues in degrees.
+    pub hue: f32,
+    /// Above 1 lightens the mid tones, below 1 darkens them.
+    pub gamma: f32,
+    pub invert: bool,
+    pub grayscale: bool,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.,
+            contrast: 1.,
+            saturation: 1.,
+            hue: 0.,
+            gamma: 1.,
+            invert: false,
+            grayscale: false,
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The same adjustments, each brought back into its range.
+    pub fn clamped(self) -> Self {
+        Self {
+            brightness: self.brightness.clamp(-1., 1.),
+            contrast: self.contrast.clamp(0., 4.),
+            saturation: self.saturation.clamp(0., 4.),
+            hue: (self.hue + 180.).rem_euclid(360.) - 180.,
+            gamma: self.gamma.clamp(0.1, 10.),
+            ..self
+        }
+    }
+
+    /// Adjustments typed as `contrast 1.2, hue -30, invert`, starting from
+    /// `self`. Names may be shortened to their first letters.
+    pub fn parse(self, text: &str) -> Result<Self, String> {
+        let mut adjustments = self;
+        for setting in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut words = setting.split_whitespace();
+            let name = words.next().unwrap_or_default().to_lowercase();
+            let value = words.next();
+            let number = || {
+                value
+                    .ok_or_else(|| format!("{} needs a value", name))?
+                    .parse::<f32>()
+                    .map_err(|e| format!("{}: {}", name, e))
+                    // Clamping would let NaN through to the shader.
+                    .and_then(|number| {
+                        if number.is_finite() {
+                            Ok(number)
+                        } else {
+                            Err(format!("{}: {} is not a number", name, number))
+                        }
+                    })
+            };
+            let flag = || match value {
+                None | Some("on") | Some("true") => Ok(true),
+                Some("off") | Some("false") => Ok(false),
+                Some(other) => Err(format!("{}: expected on or off, not {}", name, other)),
+            };
+            let matches = |full: &str| full.starts_with(&name);
+            match name.as_str() {
+                "" => {}
+                _ if matches("brightness") => adjustments.brightness = number()?,
+                _ if matches("contrast") => adjustments.contrast = number()?,
+                _ if matches("saturation") => adjustments.saturation = number()?,
+                _ if matches("hue") => adjustments.hue = number()?,
+                _ if matches("gamma") => adjustments.gamma = number()?,
+                _ if matches("invert") => adjustments.invert = flag()?,
+                _ if matches("grayscale") => adjustments.grayscale = flag()?,
+                _ => return Err(format!("unknown adjustment {}", name)),
+            }
+        }
+        Ok(adjustments.clamped())
+    }
+}
+
+/// The adjustments that change something, in the form `parse` reads.
+impl fmt::Display for Adjustments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let default = Self::default();
+        let mut settings = Vec::new();
+        let numbers = [
+            ("brightness", self.brightness, default.brightness),
+            ("contrast", self.contrast, default.contrast),
+            ("saturation", self.saturation, default.saturation),
+            ("hue", self.hue, default.hue),
+            ("gamma", self.gamma, default.gamma),
+        ];
+        for (name, value, default) in numbers {
+            if value != default {
+                settings.push(format!("{} {}", name, value));
+            }
+        }
+        if self.invert {
+            settings.push("invert".to_string());
+        }
+        if self.grayscale {
+            settings.push("grayscale".to_string());
+        }
+        write!(f, "{}", settings.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_names_values_and_flags() {
+        let adjustments = Adjustments::default()
+            .parse("contrast 1.5, h -30, invert, gray off")
+            .unwrap();
+        assert_eq!(
+            adjustments,
+            Adjustments {
+                contrast: 1.5,
+                hue: -30.,
+                invert: true,
+                ..Default::default()
+            }
+        );
+        // Other adjustments are kept.
+        let adjustments = adjustments.parse("invert off, brightness 0.25").unwrap();
+        assert_eq!(adjustments.contrast, 1.5);
+        assert_eq!(adjustments.brightness, 0.25);
+        assert!(!adjustments.invert);
+    }
+
+    #[test]
+    fn parse_brings_values_into_range() {
+        let adjustments = Adjustments::default()
+            .parse("brightness 3, gamma 0, hue 190")
+            .unwrap();
+        assert_eq!(adjustments.brightness, 1.);
+        assert_eq!(adjustments.gamma, 0.1);
+        assert_eq!(adjustments.hue, -170.);
+    }
+
+    #[test]
+    fn parse_rejects_what_it_cannot_read() {
+        for text in [
+            "contrast",
+            "sharpen 2",
+            "gamma x",
+            "gamma NaN",
+            "hue inf",
+            "invert maybe",
+        ] {
+            assert!(Adjustments::default().parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn display_reads_back() {
+        let adjustments = Adjustments {
+            saturation: 0.5,
+            gamma: 2.,
+            grayscale: true,
+            ..Default::default()
+        };
+        let text = adjustments.to_string();
+        assert_eq!(text, "saturation 0.5, gamma 2, grayscale");
+        assert_eq!(Adjustments::default().parse(&text), Ok(adjustments));
+        assert_eq!(Adjustments::default().to_string(), "");
+    }
+}
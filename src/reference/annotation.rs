@@ -0,0 +1,155 @@
+//! Vector annotations: arrows, rectangles, ellipses and freehand strokes
+//! drawn over the references. The points of an annotation attached to an
+//! image are relative to the image, so it follows the image around.
+
+use serde::{Deserialize, Serialize};
+
+/// Stroke width in board units when none is given.
+pub const DEFAULT_WIDTH: f32 = 4.;
+pub const DEFAULT_STROKE: [u8; 4] = [230, 57, 70, 255];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum Shape {
+    /// Line with a head at `to`.
+    Arrow {
+        from: [f32; 2],
+        to: [f32; 2],
+    },
+    /// Between two opposite corners.
+    Rectangle {
+        from: [f32; 2],
+        to: [f32; 2],
+    },
+    /// Inscribed in the rectangle between two opposite corners.
+    Ellipse {
+        from: [f32; 2],
+        to: [f32; 2],
+    },
+    Freehand {
+        points: Vec<[f32; 2]>,
+    },
+}
+
+impl Shape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Arrow { .. } => "arrow",
+            Shape::Rectangle { .. } => "rectangle",
+            Shape::Ellipse { .. } => "ellipse",
+            Shape::Freehand { .. } => "freehand",
+        }
+    }
+
+    /// Whether a fill color means anything for the shape.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Shape::Rectangle { .. } | Shape::Ellipse { .. })
+    }
+
+    /// Points defining the shape, in the annotation coordinates.
+    pub fn points(&self) -> Vec<[f32; 2]> {
+        match self {
+            Shape::Arrow { from, to }
+            | Shape::Rectangle { from, to }
+            | Shape::Ellipse { from, to } => vec![*from, *to],
+            Shape::Freehand { points } => points.clone(),
+        }
+    }
+
+    /// Moves the last point, where the pointer is while drawing.
+    pub fn drag_to(&mut self, point: [f32; 2]) {
+        match self {
+            Shape::Arrow { to, .. } | Shape::Rectangle { to, .. } | Shape::Ellipse { to, .. } => {
+                *to = point
+            }
+            Shape::Freehand { points } => points.push(point),
+        }
+    }
+
+    pub fn translate(&mut self, offset: [f32; 2]) {
+        let shift = |point: &mut [f32; 2]| {
+            point[0] += offset[0];
+            point[1] += offset[1];
+        };
+        match self {
+            Shape::Arrow { from, to }
+            | Shape::Rectangle { from, to }
+            | Shape::Ellipse { from, to } => {
+                shift(from);
+                shift(to);
+            }
+            Shape::Freehand { points } => points.iter_mut().for_each(shift),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(flatten)]
+    pub shape: Shape,
+    /// sRGB color and alpha of the outline.
+    pub stroke: [u8; 4],
+    /// Outline width in board units.
+    pub width: f32,
+    /// Inside color of rectangles and ellipses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<[u8; 4]>,
+    /// Image the points are relative to, the board when None.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attached_to: Option<uuid::Uuid>,
+}
+
+impl Annotation {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            stroke: DEFAULT_STROKE,
+            width: DEFAULT_WIDTH,
+            fill: None,
+            attached_to: None,
+        }
+    }
+
+    /// The two sides of the arrow head, from the tip at `to`.
+    pub fn arrow_head(&self, from: [f32; 2], to: [f32; 2]) -> [[f32; 2]; 2] {
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let length = dx.hypot(dy);
+        if length == 0. {
+            return [to, to];
+        }
+        let head = (self.width * 4.).max(12.).min(length / 2.);
+        let (ux, uy) = (dx / length, dy / length);
+        // 30 degrees each side of the shaft.
+        let (sin, cos) = (0.5, 3f32.sqrt() / 2.);
+        let side = |sin: f32| {
+            [
+                to[0] - head * (ux * cos - uy * sin),
+                to[1] - head * (uy * cos + ux * sin),
+            ]
+        };
+        [side(sin), side(-sin)]
+    }
+
+    /// Bounding box `(position, size)` on the board, stroke included, for an
+    /// annotation whose coordinates start at `origin`.
+    pub fn rect(&self, origin: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let mut points = self.shape.points();
+        if let Shape::Arrow { from, to } = self.shape {
+            points.extend(self.arrow_head(from, to));
+        }
+        let half_width = self.width / 2.;
+        let [left, top, right, bottom] = points.iter().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[left, top, right, bottom], [x, y]| {
+                [left.min(*x), top.min(*y), right.max(*x), bottom.max(*y)]
+            },
+        );
+        if left > right {
+            return (origin, [0., 0.]);
+        }
+        (
+            [origin[0] + left - half_width, origin[1] + top - half_width],
+            [right - left + self.width, bottom - top + self.width],
+        )
+    }
+}
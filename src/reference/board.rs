@@ -0,0 +1,531 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    adjust::Adjustments, annotation::Annotation, chip::Chip, crop, frame::Frame, group::Group,
+    import, metadata::Metadata, text::Text, Image, Library, Source, Storage,
+};
+
+/// Extension used for saved boards.
+pub const EXTENSION: &str = "rref";
+
+const VERSION: u32 = 13;
+
+#[derive(Debug)]
+pub enum BoardError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Image(image::ImageError),
+    Encoding(base64::DecodeError),
+    UnsupportedVersion(u32),
+    Incomplete(uuid::Uuid),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::Io(e) => write!(f, "cannot access board file: {}", e),
+            BoardError::Format(e) => write!(f, "malformed board file: {}", e),
+            BoardError::Image(e) => write!(f, "cannot decode board image: {}", e),
+            BoardError::Encoding(e) => write!(f, "corrupted image data: {}", e),
+            BoardError::UnsupportedVersion(v) => write!(f, "unsupported board version {}", v),
+            BoardError::Incomplete(id) => write!(f, "image {} has neither data nor source", id),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+impl From<std::io::Error> for BoardError {
+    fn from(e: std::io::Error) -> Self {
+        BoardError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BoardError {
+    fn from(e: serde_json::Error) -> Self {
+        BoardError::Format(e)
+    }
+}
+
+impl From<image::ImageError> for BoardError {
+    fn from(e: image::ImageError) -> Self {
+        BoardError::Image(e)
+    }
+}
+
+impl From<base64::DecodeError> for BoardError {
+    fn from(e: base64::DecodeError) -> Self {
+        BoardError::Encoding(e)
+    }
+}
+
+/// On-disk representation of a board, kept apart from `Library` so the file
+/// layout can evolve without touching the runtime types.
+#[derive(Serialize, Deserialize)]
+struct BoardFile {
+    version: u32,
+    images: Vec<ImageEntry>,
+    /// PNG encoded pixels of the embedded images, base64, once per content
+    /// hash however many images show them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    contents: BTreeMap<String, String>,
+    #[serde(default)]
+    watch_folders: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    texts: Vec<TextEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chips: Vec<ChipEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<AnnotationEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    frames: Vec<FrameEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupEntry>,
+    /// Picked colors as RGBA.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    swatches: Vec<[u8; 4]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextEntry {
+    id: uuid::Uuid,
+    #[serde(flatten)]
+    text: Text,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChipEntry {
+    id: uuid::Uuid,
+    #[serde(flatten)]
+    chip: Chip,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnnotationEntry {
+    id: uuid::Uuid,
+    #[serde(flatten)]
+    annotation: Annotation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameEntry {
+    id: uuid::Uuid,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GroupEntry {
+    id: uuid::Uuid,
+    #[serde(flatten)]
+    group: Group,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImageEntry {
+    id: uuid::Uuid,
+    position: [f32; 2],
+    /// PNG encoded pixels, base64, in boards before version 13. Newer ones
+    /// keep them in `contents` under `hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    /// Source file, relative to the board when it can be.
+    #[serde(default)]
+    source: Option<PathBuf>,
+    #[serde(default)]
+    modified: Option<SystemTime>,
+    #[serde(default)]
+    hash: Option<String>,
+    /// Kept for linked images so a missing file still takes its place.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<[u32; 2]>,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    note: String,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    hidden: bool,
+    /// Shown part of the pixels as `x, y, width, height`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crop: Option<[u32; 4]>,
+    #[serde(default, skip_serializing_if = "Adjustments::is_identity")]
+    adjustments: Adjustments,
+    /// Extracted dominant colors as RGBA.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    palette: Vec<[u8; 4]>,
+}
+
+pub fn load(path: &Path) -> Result<Library, BoardError> {
+    let file: BoardFile = serde_json::from_slice(&fs::read(path)?)?;
+    if file.version > VERSION {
+        return Err(BoardError::UnsupportedVersion(file.version));
+    }
+    let board_directory = board_directory(path)?;
+
+    let mut library = Library::new();
+    library.watch_folders = file.watch_folders;
+    library.swatches = file.swatches;
+    for entry in file.images {
+        let id = entry.id;
+        library.insert_with_key(id, load_image(entry, &file.contents, &board_directory)?);
+    }
+    for entry in file.texts {
+        library.insert_text_with_key(entry.id, entry.text);
+    }
+    for entry in file.chips {
+        library.insert_chip_with_key(entry.id, entry.chip);
+    }
+    for entry in file.annotations {
+        library.insert_annotation_with_key(entry.id, entry.annotation);
+    }
+    for entry in file.frames {
+        library.insert_frame_with_key(entry.id, entry.frame);
+    }
+    for entry in file.groups {
+        library.insert_group_with_key(entry.id, entry.group);
+    }
+
+    Ok(library)
+}
+
+fn load_image(
+    entry: ImageEntry,
+    contents: &BTreeMap<String, String>,
+    board_directory: &Path,
+) -> Result<Image, BoardError> {
+    let source = entry.source.map(|path| {
+        let path = board_directory.join(path);
+        Source {
+            path: path.canonicalize().unwrap_or_else(|_| normalize(&path)),
+            modified: entry.modified,
+        }
+    });
+
+    let data = entry
+        .data
+        .or_else(|| contents.get(entry.hash.as_ref()?).cloned());
+    let mut image = match (data, source) {
+        (Some(data), source) => {
+            let mut image = Image::new(entry.position, STANDARD.decode(data)?)?;
+            image.source = source;
+            // Describe the imported file, not the PNG it was embedded as.
+            image.metadata = entry.metadata;
+            // The embedded PNG is not the file that was imported, keep its hash.
+            if let Some(hash) = &entry.hash {
+                image.hash = hash.clone();
+            }
+            image
+        }
+        (None, Some(source)) => match fs::read(&source.path) {
+            Ok(bytes) => {
+                let mut image = Image::new(entry.position, bytes)?;
+                image.metadata.file_name = import::file_name(&source.path);
+                image.source = Some(Source {
+                    modified: import::modified(&source.path),
+                    ..source
+                });
+                image.storage = Storage::Linked;
+                // The file is read again, only what the user typed is kept.
+                image.metadata.provenance = entry.metadata.provenance;
+                image
+            }
+            Err(e) => {
+                log::warn!("{}: {}", source.path.display(), e);
+                let size = entry.size.unwrap_or([256, 256]);
+                let mut image = Image::placeholder(
+                    entry.position,
+                    size,
+                    source,
+                    entry.hash.clone().unwrap_or_default(),
+                );
+                image.metadata = entry.metadata;
+                image
+            }
+        },
+        (None, None) => return Err(BoardError::Incomplete(entry.id)),
+    };
+
+    image.tags = entry.tags;
+    image.note = entry.note;
+    image.locked = entry.locked;
+    image.hidden = entry.hidden;
+    // A linked file may have been replaced by a smaller one.
+    image.crop = entry
+        .crop
+        .and_then(|rect| crop::clamp(rect, image.full_size()));
+    image.adjustments = entry.adjustments;
+    image.palette = entry.palette;
+    Ok(image)
+}
+
+pub fn save(library: &Library, path: &Path) -> Result<(), BoardError> {
+    let board_directory = board_directory(path)?;
+
+    let mut images = Vec::with_capacity(library.len());
+    let mut contents = BTreeMap::new();
+    for (id, image) in library.iter() {
+        let linked = image.storage == Storage::Linked && image.source.is_some();
+        if !linked && !contents.contains_key(&image.hash) {
+            let mut png = Vec::new();
+            image
+                .image
+                .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+            contents.insert(image.hash.clone(), STANDARD.encode(png));
+        }
+        let [width, height] = image.full_size();
+
+        images.push(ImageEntry {
+            id: *id,
+            position: image.position,
+            data: None,
+            source: image
+                .source
+                .as_ref()
+                .map(|source| relative_path(&source.path, &board_directory)),
+            modified: image.source.as_ref().and_then(|source| source.modified),
+            hash: Some(image.hash.clone()),
+            size: linked.then_some([width, height]),
+            metadata: image.metadata.clone(),
+            tags: image.tags.clone(),
+            note: image.note.clone(),
+            locked: image.locked,
+            hidden: image.hidden,
+            crop: image.crop,
+            adjustments: image.adjustments,
+            palette: image.palette.clone(),
+        });
+    }
+
+    let file = BoardFile {
+        version: VERSION,
+        images,
+        contents,
+        watch_folders: library.watch_folders.clone(),
+        swatches: library.swatches.clone(),
+        texts: library
+            .texts()
+            .map(|(id, text)| TextEntry {
+                id: *id,
+                text: text.clone(),
+            })
+            .collect(),
+        chips: library
+            .chips()
+            .map(|(id, chip)| ChipEntry {
+                id: *id,
+                chip: chip.clone(),
+            })
+            .collect(),
+        annotations: library
+            .annotations()
+            .map(|(id, annotation)| AnnotationEntry {
+                id: *id,
+                annotation: annotation.clone(),
+            })
+            .collect(),
+        frames: library
+            .frames()
+            .map(|(id, frame)| FrameEntry {
+                id: *id,
+                frame: frame.clone(),
+            })
+            .collect(),
+        groups: library
+            .groups()
+            .map(|(id, group)| GroupEntry {
+                id: *id,
+                group: group.clone(),
+            })
+            .collect(),
+    };
+    fs::write(path, serde_json::to_vec(&file)?)?;
+
+    Ok(())
+}
+
+/// Absolute directory of the board file at `path`.
+pub fn board_directory(path: &Path) -> std::io::Result<PathBuf> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize(),
+        _ => std::env::current_dir(),
+    }
+}
+
+/// Resolves `..` in a path that does not exist, so it matches the one the
+/// file will have once it is back.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// `path` relative to `base` when both are on the same root, `path` itself
+/// otherwise.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_components: Vec<Component> = path.components().collect();
+    let base_components: Vec<Component> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if common == 0 {
+        return path.to_path_buf();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::reference::png;
+
+    /// Empty folder of its own under the system temporary folder.
+    fn scratch() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("rustyref-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        directory.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn relative_path_climbs_out_of_the_board_folder() {
+        assert_eq!(
+            relative_path(Path::new("/refs/images/a.png"), Path::new("/refs/boards")),
+            PathBuf::from("../images/a.png")
+        );
+        assert_eq!(
+            relative_path(Path::new("/refs/a.png"), Path::new("/refs")),
+            PathBuf::from("a.png")
+        );
+        assert_eq!(
+            normalize(Path::new("/refs/boards/../images/./a.png")),
+            PathBuf::from("/refs/images/a.png")
+        );
+    }
+
+    #[test]
+    fn linked_images_follow_a_moved_folder() {
+        let root = scratch();
+        let folder = root.join("refs");
+        fs::create_dir_all(folder.join("images")).unwrap();
+        fs::create_dir_all(folder.join("boards")).unwrap();
+        let pixels = RgbaImage::from_pixel(4, 3, Rgba([10, 20, 30, 255]));
+        fs::write(folder.join("images/a.png"), png(pixels)).unwrap();
+
+        let mut library = Library::new();
+        let mut image = Image::open(&folder.join("images/a.png"), [8., 9.]).unwrap();
+        image.storage = Storage::Linked;
+        image.tags = vec!["blue".to_string()];
+        let key = library.insert(image).unwrap();
+        save(&library, &folder.join("boards/b.rref")).unwrap();
+
+        let file: BoardFile =
+            serde_json::from_slice(&fs::read(folder.join("boards/b.rref")).unwrap()).unwrap();
+        assert_eq!(
+            file.images[0].source,
+            Some(PathBuf::from("../images/a.png"))
+        );
+        assert_eq!(file.images[0].data, None);
+
+        // The whole folder moves, the board still finds its files.
+        let moved = root.join("moved");
+        fs::rename(&folder, &moved).unwrap();
+        let library = load(&moved.join("boards/b.rref")).unwrap();
+        let image = library.get(&key).unwrap();
+        assert_eq!(image.missing, None);
+        assert_eq!(image.storage, Storage::Linked);
+        assert_eq!(
+            image.source.as_ref().map(|source| source.path.clone()),
+            Some(moved.join("images/a.png"))
+        );
+        assert_eq!(image.position, [8., 9.]);
+        assert_eq!(image.tags, ["blue"]);
+
+        // Gone altogether, it keeps its place and size as a placeholder.
+        fs::remove_file(moved.join("images/a.png")).unwrap();
+        let library = load(&moved.join("boards/b.rref")).unwrap();
+        let image = library.get(&key).unwrap();
+        assert_eq!(image.missing, Some([4, 3]));
+        assert_eq!(image.size(), [4., 3.]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn identical_embedded_images_are_written_once() {
+        let root = scratch();
+        let bytes = png(RgbaImage::from_pixel(4, 3, Rgba([10, 20, 30, 255])));
+        let mut library = Library::new();
+        let first = library
+            .insert(Image::new([0., 0.], bytes.clone()).unwrap())
+            .unwrap();
+        let second = library
+            .insert(Image::new([50., 0.], bytes).unwrap())
+            .unwrap();
+        save(&library, &root.join("b.rref")).unwrap();
+
+        let file: BoardFile =
+            serde_json::from_slice(&fs::read(root.join("b.rref")).unwrap()).unwrap();
+        assert_eq!(file.contents.len(), 1);
+        assert!(file.images.iter().all(|entry| entry.data.is_none()));
+
+        let library = load(&root.join("b.rref")).unwrap();
+        assert_eq!(library.get(&first).unwrap().full_size(), [4, 3]);
+        assert_eq!(library.get(&second).unwrap().position, [50., 0.]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn crops_fit_a_linked_file_that_shrank() {
+        let root = scratch();
+        let file = root.join("a.png");
+        fs::write(&file, png(RgbaImage::new(8, 8))).unwrap();
+
+        let mut library = Library::new();
+        let mut image = Image::open(&file, [0., 0.]).unwrap();
+        image.storage = Storage::Linked;
+        image.crop = Some([4, 4, 4, 4]);
+        let key = library.insert(image).unwrap();
+        save(&library, &root.join("b.rref")).unwrap();
+
+        fs::write(&file, png(RgbaImage::new(2, 2))).unwrap();
+        let library = load(&root.join("b.rref")).unwrap();
+        let [x, y, width, height] = library.get(&key).unwrap().crop.unwrap();
+        assert!(x + width <= 2 && y + height <= 2);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
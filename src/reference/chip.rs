@@ -0,0 +1,67 @@
+//! Color chips: solid colors laid on the board as items of their own, with
+//! their name and hex written under them. They need no texture.
+
+use serde::{Deserialize, Serialize};
+
+use super::{color, text::Text, Library, SPACING};
+
+/// Side of the color square, in board units.
+pub const SIZE: f32 = 160.;
+const LABEL_SIZE: f32 = 18.;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chip {
+    /// Top left corner of the color square, in the coordinates of the groups
+    /// holding the chip.
+    pub position: [f32; 2],
+    /// sRGB color and alpha.
+    pub color: [u8; 4],
+    /// Written above the hex, left out when empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+}
+
+impl Chip {
+    pub fn new(position: [f32; 2], color: [u8; 4], name: String) -> Self {
+        Self {
+            position,
+            color,
+            name,
+        }
+    }
+
+    /// Name and hex of the color, under the square.
+    pub fn label(&self) -> Text {
+        let hex = color::hex(self.color);
+        let content = match self.name.trim() {
+            "" => hex,
+            name => format!("{}\n{}", name, hex),
+        };
+        let mut label = Text::new([self.position[0], self.position[1] + SIZE], content);
+        label.font_size = LABEL_SIZE;
+        label.color = [40, 40, 40, 255];
+        // The renderer draws the strip behind it, as wide as the chip.
+        label.background = None;
+        label
+    }
+
+    /// The square and the label under it, as wide as the square.
+    pub fn size(&self) -> [f32; 2] {
+        let [width, height] = self.label().size();
+        [SIZE.max(width), SIZE + height]
+    }
+}
+
+/// Lays `colors` out as unnamed chips in a row from the board point
+/// `position`. Returns the new chips.
+pub fn row(library: &mut Library, position: [f32; 2], colors: &[[u8; 4]]) -> Vec<uuid::Uuid> {
+    let [mut x, y] = position;
+    colors
+        .iter()
+        .map(|color| {
+            let chip = Chip::new([x, y], *color, String::new());
+            x += chip.size()[0] + SPACING;
+            library.insert_chip(chip)
+        })
+        .collect()
+}
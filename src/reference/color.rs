@@ -0,0 +1,143 @@
+//! Colors picked from the pixels of images, and the ways to write them down.
+
+use image::GenericImageView;
+
+use super::Library;
+
+/// A pixel read from the decoded image, not from the screen.
+pub struct Picked {
+    pub image_id: uuid::Uuid,
+    /// Pixel of the whole image, whatever its crop.
+    pub pixel: [u32; 2],
+    pub color: [u8; 4],
+}
+
+/// Pixel of the visible image under the board point `position`, through
+/// the groups turning the image and its crop.
+pub fn pick(library: &Library, position: [f32; 2]) -> Option<Picked> {
+    let image_id = library.image_at(position)?;
+    let image = library
+        .get(&image_id)
+        .filter(|image| image.missing.is_none())?;
+    let local = library.to_local(&image_id, position);
+    let [x, y, width, height] = image.crop_rect();
+    let [px, py] = [
+        local[0] - image.position[0] + x as f32,
+        local[1] - image.position[1] + y as f32,
+    ]
+    .map(f32::floor);
+    // Edges of the crop land on the last pixel shown.
+    let pixel = [
+        (px.max(0.) as u32).clamp(x, x + width - 1),
+        (py.max(0.) as u32).clamp(y, y + height - 1),
+    ];
+    Some(Picked {
+        image_id,
+        pixel,
+        color: image.image.get_pixel(pixel[0], pixel[1]).0,
+    })
+}
+
+/// Colors of the `size` x `size` pixels centered on `pixel` of the image
+/// `image_id`, row by row. None for the pixels out of its crop.
+pub fn neighborhood(
+    library: &Library,
+    image_id: &uuid::Uuid,
+    pixel: [u32; 2],
+    size: u32,
+) -> Vec<Option<[u8; 4]>> {
+    let Some(image) = library.get(image_id) else {
+        return Vec::new();
+    };
+    let [x, y, width, height] = image.crop_rect();
+    let half = (size / 2) as i64;
+    let mut colors = Vec::with_capacity((size * size) as usize);
+    for dy in -half..=half {
+        for dx in -half..=half {
+            let [px, py] = [pixel[0] as i64 + dx, pixel[1] as i64 + dy];
+            let inside = (x as i64..(x + width) as i64).contains(&px)
+                && (y as i64..(y + height) as i64).contains(&py);
+            colors.push(inside.then(|| image.image.get_pixel(px as u32, py as u32).0));
+        }
+    }
+    colors
+}
+
+/// `#rrggbb`, with the alpha after when the color is not opaque.
+pub fn hex([r, g, b, a]: [u8; 4]) -> String {
+    match a {
+        255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        a => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    }
+}
+
+/// Hue in degrees, saturation and value from 0 to 1.
+pub fn hsv([r, g, b, _]: [u8; 4]) -> [f32; 3] {
+    let [r, g, b] = [r, g, b].map(|channel| channel as f32 / 255.);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+    let saturation = if max == 0. { 0. } else { delta / max };
+    [hue, saturation, max]
+}
+
+/// Lightness from 0 to 1 and the two opponent axes of the Oklab space, where
+/// distances follow how different colors look.
+pub fn oklab([r, g, b, _]: [u8; 4]) -> [f32; 3] {
+    let [r, g, b] = [r, g, b].map(|channel| {
+        let c = channel as f32 / 255.;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let l = (0.4122215 * r + 0.5363325 * g + 0.051446 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.107397 * b).cbrt();
+    let s = (0.0883025 * r + 0.2817188 * g + 0.6299787 * b).cbrt();
+    [
+        0.2104543 * l + 0.7936178 * m - 0.004072 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904 * l + 0.7827718 * m - 0.8086758 * s,
+    ]
+}
+
+/// Lightness from 0 to 1, chroma and hue in degrees of the Oklab space.
+pub fn oklch(color: [u8; 4]) -> [f32; 3] {
+    let [lightness, a, b] = oklab(color);
+    let chroma = a.hypot(b);
+    let hue = if chroma < 1e-4 {
+        0.
+    } else {
+        b.atan2(a).to_degrees().rem_euclid(360.)
+    };
+    [lightness, chroma, hue]
+}
+
+/// The color in hex, RGB, HSV and OKLCH on one line.
+pub fn describe(color: [u8; 4]) -> String {
+    let [r, g, b, _] = color;
+    let [hue, saturation, value] = hsv(color);
+    let [lightness, chroma, oklch_hue] = oklch(color);
+    format!(
+        "{}  rgb({}, {}, {})  hsv({:.0}, {:.0}%, {:.0}%)  oklch({:.3} {:.3} {:.1})",
+        hex(color),
+        r,
+        g,
+        b,
+        hue,
+        saturation * 100.,
+        value * 100.,
+        lightness,
+        chroma,
+        oklch_hue
+    )
+}
@@ -0,0 +1,232 @@
+//! Non-destructive cropping. An image keeps all its pixels and shows the
+//! rectangle `Image::crop` of them, in pixels of the decoded image.
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+use super::{Image, Library};
+
+/// Largest difference of a channel, out of 255, for a pixel to count as
+/// part of the border when trimming.
+pub const DEFAULT_TOLERANCE: u8 = 8;
+
+/// Handles of the crop rectangle, as the edges they move: left, top, right
+/// and bottom.
+pub const HANDLES: [[bool; 4]; 8] = [
+    [true, true, false, false],
+    [false, true, false, false],
+    [false, true, true, false],
+    [false, false, true, false],
+    [false, false, true, true],
+    [false, false, false, true],
+    [true, false, false, true],
+    [true, false, false, false],
+];
+
+impl Image {
+    /// Size of all the pixels, whatever the crop.
+    pub fn full_size(&self) -> [u32; 2] {
+        self.missing.unwrap_or_else(|| {
+            let (width, height) = self.image.dimensions();
+            [width, height]
+        })
+    }
+
+    /// The shown rectangle `[x, y, width, height]` in pixels.
+    pub fn crop_rect(&self) -> [u32; 4] {
+        let [width, height] = self.full_size();
+        self.crop.unwrap_or([0, 0, width, height])
+    }
+
+    /// Texture coordinates `[u0, v0, u1, v1]` of the shown rectangle.
+    pub fn uv(&self) -> [f32; 4] {
+        let [x, y, width, height] = self.crop_rect().map(|value| value as f32);
+        let [full_width, full_height] = self.full_size().map(|value| value as f32);
+        [
+            x / full_width,
+            y / full_height,
+            (x + width) / full_width,
+            (y + height) / full_height,
+        ]
+    }
+
+    /// Pixels of the shown rectangle.
+    pub fn cropped(&self) -> DynamicImage {
+        match self.crop {
+            Some([x, y, width, height]) if self.missing.is_none() => {
+                self.image.crop_imm(x, y, width, height)
+            }
+            _ => (*self.image).clone(),
+        }
+    }
+}
+
+/// `crop` made to fit in an image of `size`, None when it covers it all.
+pub fn clamp(crop: [u32; 4], size: [u32; 2]) -> Option<[u32; 4]> {
+    let [x, y, width, height] = crop;
+    let x = x.min(size[0] - 1);
+    let y = y.min(size[1] - 1);
+    let crop = [
+        x,
+        y,
+        width.clamp(1, size[0] - x),
+        height.clamp(1, size[1] - y),
+    ];
+    (crop != [0, 0, size[0], size[1]]).then_some(crop)
+}
+
+/// Shows the rectangle `crop` of the image `key`, or all of it. The pixels
+/// that stay in view, and the annotations on them, do not move on the board.
+pub fn set(library: &mut Library, key: &uuid::Uuid, crop: Option<[u32; 4]>) {
+    let Some(image) = library.get_mut(key) else {
+        return;
+    };
+    let [old_x, old_y, _, _] = image.crop_rect();
+    image.crop = crop.and_then(|crop| clamp(crop, image.full_size()));
+    let [x, y, _, _] = image.crop_rect();
+    let offset = [x as f32 - old_x as f32, y as f32 - old_y as f32];
+    image.position[0] += offset[0];
+    image.position[1] += offset[1];
+
+    for annotation_id in library.annotations_on(key) {
+        if let Some(annotation) = library.get_annotation_mut(&annotation_id) {
+            annotation.shape.translate([-offset[0], -offset[1]]);
+        }
+    }
+}
+
+/// Crop of an image of `size` once the `edges` of `crop` are dragged to
+/// `point`, in pixels of the image.
+pub fn drag(crop: [u32; 4], size: [u32; 2], edges: [bool; 4], point: [f32; 2]) -> [u32; 4] {
+    let [x, y, width, height] = crop;
+    let [mut left, mut top, mut right, mut bottom] = [x, y, x + width, y + height];
+    let [px, py] = [
+        point[0].round().clamp(0., size[0] as f32) as u32,
+        point[1].round().clamp(0., size[1] as f32) as u32,
+    ];
+    let [move_left, move_top, move_right, move_bottom] = edges;
+    if move_left {
+        left = px.min(right - 1);
+    }
+    if move_top {
+        top = py.min(bottom - 1);
+    }
+    if move_right {
+        right = px.max(left + 1);
+    }
+    if move_bottom {
+        bottom = py.max(top + 1);
+    }
+    [left, top, right - left, bottom - top]
+}
+
+/// Where the handle moving `edges` sits, relative to the top left corner of
+/// a crop of `size`.
+pub fn handle_position(edges: [bool; 4], size: [f32; 2]) -> [f32; 2] {
+    let [left, top, right, bottom] = edges;
+    let along = |low: bool, high: bool, length: f32| match (low, high) {
+        (true, _) => 0.,
+        (_, true) => length,
+        _ => length / 2.,
+    };
+    [along(left, right, size[0]), along(top, bottom, size[1])]
+}
+
+/// Crop around the content of `image`, leaving out the borders of the color
+/// of its top left pixel, or fully transparent, give or take `tolerance`.
+/// None when there is no border, or nothing but border.
+pub fn content(image: &DynamicImage, tolerance: u8) -> Option<[u32; 4]> {
+    let pixels = image.to_rgba8();
+    let (width, height) = pixels.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let border = *pixels.get_pixel(0, 0);
+    let transparent = |pixel: &Rgba<u8>| pixel[3] <= tolerance;
+    let empty = |x: u32, y: u32| {
+        let pixel = pixels.get_pixel(x, y);
+        (transparent(&border) && transparent(pixel))
+            || pixel
+                .0
+                .iter()
+                .zip(border.0)
+                .all(|(channel, expected)| channel.abs_diff(expected) <= tolerance)
+    };
+    let row = |y: u32| (0..width).any(|x| !empty(x, y));
+    let top = (0..height).find(|y| row(*y))?;
+    let bottom = (top..height).rev().find(|y| row(*y))? + 1;
+    let column = |x: u32| (top..bottom).any(|y| !empty(x, y));
+    let left = (0..width).find(|x| column(*x))?;
+    let right = (left..width).rev().find(|x| column(*x))? + 1;
+    clamp([left, top, right - left, bottom - top], [width, height])
+}
+
+/// Crops the image `key` around its content, see `content`. Returns whether
+/// the crop changed.
+pub fn trim(library: &mut Library, key: &uuid::Uuid, tolerance: u8) -> bool {
+    let Some(crop) = library
+        .get(key)
+        .filter(|image| image.missing.is_none())
+        .and_then(|image| {
+            content(&image.image, tolerance).filter(|crop| image.crop != Some(*crop))
+        })
+    else {
+        return false;
+    };
+    set(library, key, Some(crop));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::reference::png;
+
+    /// `width` x `height` of `background` with a red block at `[x, y, w, h]`.
+    fn block(width: u32, height: u32, background: [u8; 4], rect: [u32; 4]) -> RgbaImage {
+        let [x, y, w, h] = rect;
+        RgbaImage::from_fn(width, height, |px, py| {
+            let inside = (x..x + w).contains(&px) && (y..y + h).contains(&py);
+            Rgba(if inside { [255, 0, 0, 255] } else { background })
+        })
+    }
+
+    #[test]
+    fn content_leaves_out_transparent_borders() {
+        let image = DynamicImage::ImageRgba8(block(10, 8, [0, 0, 0, 0], [3, 2, 4, 5]));
+        assert_eq!(content(&image, 0), Some([3, 2, 4, 5]));
+    }
+
+    #[test]
+    fn content_leaves_out_borders_of_the_corner_color() {
+        let mut pixels = block(12, 12, [255, 255, 255, 255], [1, 4, 10, 3]);
+        // Off-white noise within the tolerance is still border.
+        pixels.put_pixel(0, 11, Rgba([250, 252, 255, 255]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        assert_eq!(content(&image, 8), Some([1, 4, 10, 3]));
+        assert_eq!(content(&image, 0), Some([0, 4, 11, 8]));
+    }
+
+    #[test]
+    fn content_is_none_without_border_or_content() {
+        let full = DynamicImage::ImageRgba8(block(6, 6, [0, 0, 0, 0], [0, 0, 6, 6]));
+        assert_eq!(content(&full, 0), None);
+        let empty = DynamicImage::ImageRgba8(block(6, 6, [9, 9, 9, 255], [0, 0, 0, 0]));
+        assert_eq!(content(&empty, 0), None);
+    }
+
+    #[test]
+    fn trim_crops_once() {
+        let mut library = Library::new();
+        let pixels = block(20, 10, [0, 0, 0, 0], [5, 5, 10, 2]);
+        let image = Image::new([0., 0.], png(pixels)).unwrap();
+        let key = library.insert(image).unwrap();
+
+        assert!(trim(&mut library, &key, DEFAULT_TOLERANCE));
+        let image = library.get(&key).unwrap();
+        assert_eq!(image.crop, Some([5, 5, 10, 2]));
+        assert_eq!(image.size(), [10., 2.]);
+        assert!(!trim(&mut library, &key, DEFAULT_TOLERANCE));
+    }
+}
@@ -0,0 +1,192 @@
+//! Frames: named regions of the board grouping the images, texts, chips and
+//! annotations laid out in them. A frame lists its contents, so they move
+//! with it and hide when it is collapsed.
+
+use serde::{Deserialize, Serialize};
+
+use super::{text::Text, Library};
+
+/// Font size of the title above a frame, in board units.
+pub const TITLE_SIZE: f32 = 24.;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub name: String,
+    /// Top left corner of the frame, the title sits above it.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Only the title shows, the contents are hidden.
+    #[serde(default)]
+    pub collapsed: bool,
+    /// Images, texts, chips and annotations in the frame.
+    #[serde(default)]
+    pub contents: Vec<uuid::Uuid>,
+}
+
+impl Frame {
+    pub fn new(name: String, position: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            name,
+            position,
+            size,
+            collapsed: false,
+            contents: Vec::new(),
+        }
+    }
+
+    /// Title text drawn above the frame.
+    pub fn title(&self) -> Text {
+        let label = if self.collapsed {
+            format!("\u{25b8} {} ({})", self.name, self.contents.len())
+        } else {
+            self.name.clone()
+        };
+        let mut title = Text::new(self.position, label);
+        title.font_size = TITLE_SIZE;
+        title.background = None;
+        title.position[1] -= title.size()[1];
+        title
+    }
+
+    /// Bar behind the title, as wide as the frame unless collapsed.
+    pub fn title_rect(&self) -> ([f32; 2], [f32; 2]) {
+        let title = self.title();
+        let [width, height] = title.size();
+        let width = if self.collapsed {
+            width
+        } else {
+            width.max(self.size[0])
+        };
+        (title.position, [width, height])
+    }
+
+    /// What the frame covers on the board, title included.
+    pub fn rect(&self) -> ([f32; 2], [f32; 2]) {
+        let (position, size) = self.title_rect();
+        if self.collapsed {
+            return (position, size);
+        }
+        (position, [size[0], size[1] + self.size[1]])
+    }
+
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.position[0]
+            && point[0] <= self.position[0] + self.size[0]
+            && point[1] >= self.position[1]
+            && point[1] <= self.position[1] + self.size[1]
+    }
+}
+
+/// Puts in the frame `key` every item whose center is inside it, taking them
+/// out of the frames they were in.
+pub fn enclose(library: &mut Library, key: &uuid::Uuid) {
+    let Some(frame) = library.get_frame(key) else {
+        return;
+    };
+    let inside: Vec<uuid::Uuid> = library
+        .items()
+        .filter(|item| {
+            library.rect(item).is_some_and(|(position, size)| {
+                frame.contains([position[0] + size[0] / 2., position[1] + size[1] / 2.])
+            })
+        })
+        .collect();
+    for item in &inside {
+        adopt(library, key, *item);
+    }
+}
+
+/// Moves `item` into the frame `key`.
+pub fn adopt(library: &mut Library, key: &uuid::Uuid, item: uuid::Uuid) {
+    for (_, frame) in library.frames_mut() {
+        frame.contents.retain(|content| *content != item);
+    }
+    if let Some(frame) = library.get_frame_mut(key) {
+        frame.contents.push(item);
+    }
+}
+
+/// Expanded frame whose body holds the board point `position`.
+pub fn frame_at(library: &Library, position: [f32; 2]) -> Option<uuid::Uuid> {
+    library
+        .frames()
+        .find(|(_, frame)| !frame.collapsed && frame.contains(position))
+        .map(|(key, _)| *key)
+}
+
+/// Frame whose title bar is under the board point `position`.
+pub fn title_at(library: &Library, position: [f32; 2]) -> Option<uuid::Uuid> {
+    library
+        .frames()
+        .find(|(_, frame)| {
+            let (origin, size) = frame.title_rect();
+            position[0] >= origin[0]
+                && position[0] <= origin[0] + size[0]
+                && position[1] >= origin[1]
+                && position[1] <= origin[1] + size[1]
+        })
+        .map(|(key, _)| *key)
+}
+
+/// Frames in reading order, top to bottom then left to right.
+pub fn sorted(library: &Library) -> Vec<uuid::Uuid> {
+    let mut frames: Vec<_> = library.frames().collect();
+    frames.sort_by(|(_, a), (_, b)| {
+        a.position[1]
+            .total_cmp(&b.position[1])
+            .then(a.position[0].total_cmp(&b.position[0]))
+    });
+    frames.into_iter().map(|(key, _)| *key).collect()
+}
+
+/// Frame named `name`, ignoring case.
+pub fn find(library: &Library, name: &str) -> Option<uuid::Uuid> {
+    library
+        .frames()
+        .find(|(_, frame)| frame.name.eq_ignore_ascii_case(name))
+        .map(|(key, _)| *key)
+}
+
+/// Moves the frame `key` and its contents but locked images by `offset`.
+/// Returns every item that moved, the frame included.
+pub fn translate(library: &mut Library, key: &uuid::Uuid, offset: [f32; 2]) -> Vec<uuid::Uuid> {
+    let Some(frame) = library.get_frame_mut(key) else {
+        return Vec::new();
+    };
+    frame.position[0] += offset[0];
+    frame.position[1] += offset[1];
+    let contents = frame.contents.clone();
+
+    let mut moved = vec![*key];
+    for item in contents {
+        // Items in a turned or scaled group move along its own axes.
+        let offset = library
+            .world(&item)
+            .inverse()
+            .map_or(offset, |inverse| inverse.apply_vector(offset));
+        let shift = |position: &mut [f32; 2]| {
+            position[0] += offset[0];
+            position[1] += offset[1];
+        };
+        if let Some(image) = library.get_mut(&item) {
+            // Locked images stay where they are.
+            if image.locked {
+                continue;
+            }
+            shift(&mut image.position);
+        } else if let Some(text) = library.get_text_mut(&item) {
+            shift(&mut text.position);
+        } else if let Some(chip) = library.get_chip_mut(&item) {
+            shift(&mut chip.position);
+        } else if let Some(annotation) = library.get_annotation_mut(&item) {
+            // Attached annotations already follow their image.
+            if annotation.attached_to.is_none() {
+                annotation.shape.translate(offset);
+            }
+        } else {
+            continue;
+        }
+        moved.push(item);
+    }
+    moved
+}
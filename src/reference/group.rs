@@ -0,0 +1,200 @@
+//! Groups: images, texts, chips, annotations and other groups moved, scaled
+//! and rotated as one. The children of a group are laid out in its own
+//! coordinates, its transform places them in the coordinates of its parent.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{transform::Transform, Library};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Images, texts, chips, annotations attached to no image and groups.
+    pub children: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug)]
+pub enum GroupError {
+    Empty,
+    NotAnItem(uuid::Uuid),
+    /// The items are not all in the same group.
+    Scattered,
+    NotAGroup(uuid::Uuid),
+    /// A group turned or scaled whose children cannot take its transform.
+    Transformed(String),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::Empty => write!(f, "nothing to group"),
+            GroupError::NotAnItem(id) => write!(f, "{} cannot be grouped", id),
+            GroupError::Scattered => write!(f, "the items belong to different groups"),
+            GroupError::NotAGroup(id) => write!(f, "{} is not a group", id),
+            GroupError::Transformed(name) => write!(
+                f,
+                "{} is rotated or scaled, only groups can keep its transform",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+/// Whether `key` can be put in a group.
+fn groupable(library: &Library, key: &uuid::Uuid) -> bool {
+    library.get(key).is_some()
+        || library.get_text(key).is_some()
+        || library.get_chip(key).is_some()
+        || library.get_group(key).is_some()
+        || library
+            .get_annotation(key)
+            .is_some_and(|annotation| annotation.attached_to.is_none())
+}
+
+/// Makes a group of `keys`, which must share their parent. The group takes
+/// their place in it.
+pub fn group(
+    library: &mut Library,
+    name: Option<String>,
+    keys: &[uuid::Uuid],
+) -> Result<uuid::Uuid, GroupError> {
+    let first = keys.first().ok_or(GroupError::Empty)?;
+    if let Some(key) = keys.iter().find(|key| !groupable(library, key)) {
+        return Err(GroupError::NotAnItem(*key));
+    }
+    let parent = library.parent_of(first);
+    if keys.iter().any(|key| library.parent_of(key) != parent) {
+        return Err(GroupError::Scattered);
+    }
+
+    let name = name.unwrap_or_else(|| format!("Group {}", library.groups().count() + 1));
+    let mut children = Vec::new();
+    for key in keys {
+        if !children.contains(key) {
+            children.push(*key);
+        }
+    }
+    let group_id = uuid::Uuid::new_v4();
+    if let Some(parent) = parent.and_then(|parent| library.get_group_mut(&parent)) {
+        parent.children.retain(|child| !children.contains(child));
+        parent.children.push(group_id);
+    }
+    library.insert_group_with_key(
+        group_id,
+        Group {
+            name,
+            transform: Transform::IDENTITY,
+            children,
+        },
+    );
+    Ok(group_id)
+}
+
+/// Dissolves the group `key`, its children go to its parent where they stay
+/// on the board. Returns the children.
+pub fn ungroup(library: &mut Library, key: &uuid::Uuid) -> Result<Vec<uuid::Uuid>, GroupError> {
+    let group = library.get_group(key).ok_or(GroupError::NotAGroup(*key))?;
+    let leaves = group
+        .children
+        .iter()
+        .any(|child| library.get_group(child).is_none());
+    if leaves && !group.transform.is_translation() {
+        return Err(GroupError::Transformed(group.name.clone()));
+    }
+
+    let parent = library.parent_of(key);
+    let Some(Group {
+        transform,
+        children,
+        ..
+    }) = library.remove_group(key)
+    else {
+        return Err(GroupError::NotAGroup(*key));
+    };
+    for child in &children {
+        if let Some(inner) = library.get_group_mut(child) {
+            inner.transform = transform.then(&inner.transform);
+        } else if let Some(image) = library.get_mut(child) {
+            image.position = transform.apply(image.position);
+        } else if let Some(text) = library.get_text_mut(child) {
+            text.position = transform.apply(text.position);
+        } else if let Some(chip) = library.get_chip_mut(child) {
+            chip.position = transform.apply(chip.position);
+        } else if let Some(annotation) = library.get_annotation_mut(child) {
+            annotation.shape.translate(transform.offset());
+        }
+    }
+    if let Some(parent) = parent.and_then(|parent| library.get_group_mut(&parent)) {
+        parent.children.retain(|child| child != key);
+        parent.children.extend(&children);
+    }
+    Ok(children)
+}
+
+/// Images, texts, chips and annotations in the group `key` and the groups in it.
+pub fn members(library: &Library, key: &uuid::Uuid) -> Vec<uuid::Uuid> {
+    let Some(group) = library.get_group(key) else {
+        return Vec::new();
+    };
+    group
+        .children
+        .iter()
+        .flat_map(|child| match library.get_group(child) {
+            Some(_) => members(library, child),
+            None => vec![*child],
+        })
+        .collect()
+}
+
+/// Whether the group `key` holds a locked image, which keeps it in place.
+pub fn locked(library: &Library, key: &uuid::Uuid) -> bool {
+    members(library, key)
+        .iter()
+        .any(|member| library.get(member).is_some_and(|image| image.locked))
+}
+
+/// Applies `change`, a transform of the board, to the group `key`.
+fn transform(library: &mut Library, key: &uuid::Uuid, change: Transform) {
+    let parent = library.world(key);
+    let Some(inverse) = parent.inverse() else {
+        return;
+    };
+    if let Some(group) = library.get_group_mut(key) {
+        group.transform = inverse.then(&change).then(&parent).then(&group.transform);
+    }
+}
+
+/// Center of the group `key` on the board.
+fn center(library: &Library, key: &uuid::Uuid) -> Option<[f32; 2]> {
+    let (position, size) = library.rect(key)?;
+    Some([position[0] + size[0] / 2., position[1] + size[1] / 2.])
+}
+
+/// Moves the group `key` by `offset` on the board.
+pub fn translate(library: &mut Library, key: &uuid::Uuid, offset: [f32; 2]) {
+    transform(library, key, Transform::translation(offset));
+}
+
+/// Turns the group `key` by `degrees` clockwise around its center.
+pub fn rotate(library: &mut Library, key: &uuid::Uuid, degrees: f32) {
+    if let Some(center) = center(library, key) {
+        let change = Transform::rotation_about(degrees.to_radians(), center);
+        transform(library, key, change);
+    }
+}
+
+/// Scales the group `key` by `factor` around its center.
+pub fn scale(library: &mut Library, key: &uuid::Uuid, factor: f32) {
+    if factor <= 0. {
+        return;
+    }
+    if let Some(center) = center(library, key) {
+        transform(library, key, Transform::scaling_about(factor, center));
+    }
+}
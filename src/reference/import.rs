@@ -0,0 +1,64 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use image::ImageFormat;
+
+use super::{board, Image, Source};
+
+impl Image {
+    /// Decodes the image file at `path` and places it at `position`. The
+    /// image remembers the file so it can be reloaded when it changes.
+    pub fn open(path: &Path, position: [f32; 2]) -> image::ImageResult<Self> {
+        let path = path.canonicalize()?;
+        // Read before decoding so a write racing the import reloads it again.
+        let modified = modified(&path);
+        let mut image = Image::new(position, fs::read(&path)?)?;
+        image.metadata.file_name = file_name(&path);
+        image.source = Some(Source { path, modified });
+        Ok(image)
+    }
+}
+
+pub(super) fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Last modification time of the file at `path`, if the platform has one.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Whether `path` has the extension of an image format we can decode.
+pub fn is_image_path(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.can_read())
+}
+
+pub fn is_board_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(board::EXTENSION))
+}
+
+/// Replaces every directory in `paths` by the image files it contains, sorted
+/// by name so batches land on the board in a predictable order.
+pub fn expand_paths(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut images: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && is_image_path(path))
+                .collect();
+            images.sort();
+            expanded.extend(images);
+        } else {
+            expanded.push(path.clone());
+        }
+    }
+    Ok(expanded)
+}
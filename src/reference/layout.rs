@@ -0,0 +1,45 @@
+use super::{Library, SPACING};
+
+/// Lays images out left to right, starting a new row every `columns` images.
+pub struct Grid {
+    origin: [f32; 2],
+    columns: usize,
+    count: usize,
+    cursor: [f32; 2],
+    row_height: f32,
+}
+
+impl Grid {
+    pub fn new(origin: [f32; 2], columns: usize) -> Self {
+        Self {
+            origin,
+            columns: columns.max(1),
+            count: 0,
+            cursor: origin,
+            row_height: 0.,
+        }
+    }
+
+    /// Grid starting under everything already on the board.
+    pub fn below(library: &Library, columns: usize) -> Self {
+        let origin = match library.bounds() {
+            Some([x, y, _, height]) => [x, y + height + SPACING],
+            None => [0., 0.],
+        };
+        Self::new(origin, columns)
+    }
+
+    /// Position of the next image of `size`.
+    pub fn place(&mut self, size: [f32; 2]) -> [f32; 2] {
+        if self.count > 0 && self.count.is_multiple_of(self.columns) {
+            self.cursor = [self.origin[0], self.cursor[1] + self.row_height + SPACING];
+            self.row_height = 0.;
+        }
+
+        let position = self.cursor;
+        self.cursor[0] += size[0] + SPACING;
+        self.row_height = self.row_height.max(size[1]);
+        self.count += 1;
+        position
+    }
+}
@@ -0,0 +1,181 @@
+//! What is known about an image besides its pixels: facts read from the file
+//! at import, and provenance fields the user fills in to track rights.
+
+use std::io::Cursor;
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// Size of the imported file in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// EXIF make and model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<String>,
+    /// EXIF date the picture was taken, as written by the camera.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub taken: Option<String>,
+    /// EXIF orientation tag, 1 to 8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u32>,
+    pub provenance: Provenance,
+}
+
+/// Where a reference comes from, edited by the user.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Provenance {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+impl Metadata {
+    /// Reads what the encoded file `bytes` say about themselves.
+    pub fn read(bytes: &[u8]) -> Self {
+        let mut metadata = Metadata {
+            file_size: Some(bytes.len() as u64),
+            format: image::guess_format(bytes)
+                .ok()
+                .map(|format| format!("{:?}", format).to_uppercase()),
+            ..Default::default()
+        };
+
+        let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(bytes)) else {
+            return metadata;
+        };
+        let text = |tag| {
+            let field = exif.get_field(tag, exif::In::PRIMARY)?;
+            match &field.value {
+                exif::Value::Ascii(values) => values
+                    .first()
+                    .map(|value| String::from_utf8_lossy(value).trim().to_string())
+                    .filter(|value| !value.is_empty()),
+                _ => None,
+            }
+        };
+
+        metadata.camera = match (text(exif::Tag::Make), text(exif::Tag::Model)) {
+            // Models usually repeat the make already.
+            (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        };
+        metadata.taken = text(exif::Tag::DateTimeOriginal).or_else(|| text(exif::Tag::DateTime));
+        metadata.orientation = exif
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0));
+        metadata
+    }
+
+    /// Labelled fields that are set, for listings and search.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let provenance = &self.provenance;
+        [
+            ("file name", self.file_name.clone()),
+            (
+                "file size",
+                self.file_size.map(|size| format!("{} bytes", size)),
+            ),
+            ("format", self.format.clone()),
+            ("camera", self.camera.clone()),
+            ("taken", self.taken.clone()),
+            ("orientation", self.orientation.map(|o| o.to_string())),
+            ("source url", provenance.source_url.clone()),
+            ("author", provenance.author.clone()),
+            ("license", provenance.license.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(label, value)| value.map(|value| (label, value)))
+        .collect()
+    }
+}
+
+/// Turns pixels stored as the camera sensor saw them upright, following the
+/// EXIF `orientation` tag.
+pub fn apply_orientation(image: DynamicImage, orientation: Option<u32>) -> DynamicImage {
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    const A: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const B: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const C: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const D: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const E: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const F: Rgba<u8> = Rgba([128, 128, 128, 255]);
+
+    /// The upright picture, 3 pixels wide and 2 high:
+    ///
+    /// ```text
+    /// A B C
+    /// D E F
+    /// ```
+    const UPRIGHT: [[Rgba<u8>; 3]; 2] = [[A, B, C], [D, E, F]];
+
+    /// `rows` as an image.
+    fn image(rows: &[&[Rgba<u8>]]) -> DynamicImage {
+        let pixels = RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            rows[y as usize][x as usize]
+        });
+        DynamicImage::ImageRgba8(pixels)
+    }
+
+    #[test]
+    fn every_orientation_comes_out_upright() {
+        // How a camera stores the upright picture under each tag.
+        let stored: [(u32, &[&[Rgba<u8>]]); 8] = [
+            (1, &[&[A, B, C], &[D, E, F]]),
+            (2, &[&[C, B, A], &[F, E, D]]),
+            (3, &[&[F, E, D], &[C, B, A]]),
+            (4, &[&[D, E, F], &[A, B, C]]),
+            (5, &[&[A, D], &[B, E], &[C, F]]),
+            (6, &[&[C, F], &[B, E], &[A, D]]),
+            (7, &[&[F, C], &[E, B], &[D, A]]),
+            (8, &[&[D, A], &[E, B], &[F, C]]),
+        ];
+        let upright = image(&[&UPRIGHT[0], &UPRIGHT[1]]);
+        for (orientation, rows) in stored {
+            let turned = apply_orientation(image(rows), Some(orientation));
+            assert_eq!(
+                turned.to_rgba8(),
+                upright.to_rgba8(),
+                "orientation {}",
+                orientation
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_orientations_are_left_alone() {
+        let upright = image(&[&UPRIGHT[0], &UPRIGHT[1]]);
+        for orientation in [None, Some(0), Some(9)] {
+            let result = apply_orientation(upright.clone(), orientation);
+            assert_eq!(result.to_rgba8(), upright.to_rgba8());
+        }
+    }
+}
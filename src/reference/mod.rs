@@ -1,48 +1,792 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::SystemTime,
+};
 
 use image::DynamicImage;
+use sha2::{Digest, Sha256};
+
+use adjust::Adjustments;
+use annotation::Annotation;
+use chip::Chip;
+use frame::Frame;
+use group::Group;
+use metadata::Metadata;
+use text::Text;
+use transform::Transform;
+
+pub mod adjust;
+pub mod annotation;
+pub mod board;
+pub mod chip;
+pub mod color;
+pub mod crop;
+pub mod frame;
+pub mod group;
+pub mod import;
+pub mod layout;
+pub mod metadata;
+pub mod palette;
+pub mod relink;
+pub mod search;
+pub mod similar;
+pub mod text;
+pub mod transform;
+
+/// Gap left between images placed automatically.
+pub const SPACING: f32 = 16.;
+
+/// Content key shared by every missing file placeholder, they all hold the
+/// same checker pixels.
+const PLACEHOLDER_KEY: &str = "placeholder";
+
+/// File an image was imported from, used to reload it when it changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Modification time of the file when it was last decoded.
+    pub modified: Option<SystemTime>,
+}
+
+/// How an image is written to a saved board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Storage {
+    /// The pixels are stored in the board file.
+    #[default]
+    Embedded,
+    /// Only the path of the source file is stored.
+    Linked,
+}
 
 pub struct Image {
+    /// Top left corner, in the coordinates of the groups holding the image.
     pub position: [f32; 2],
-    pub image: DynamicImage,
+    /// Decoded pixels, shared with every image of the library that has the
+    /// same content.
+    pub image: Arc<DynamicImage>,
+    pub source: Option<Source>,
+    /// Hex SHA-256 of the encoded bytes the image was imported from.
+    pub hash: String,
+    /// Difference hash of the pixels, see `similar`. None for placeholders.
+    pub perceptual_hash: Option<u64>,
+    pub metadata: Metadata,
+    pub tags: Vec<String>,
+    pub note: String,
+    pub storage: Storage,
+    /// Set when the linked file could not be found. `image` then only holds a
+    /// placeholder and this is the size recorded in the board.
+    pub missing: Option<[u32; 2]>,
+    /// Cannot be selected or moved with the mouse.
+    pub locked: bool,
+    /// Not drawn, but kept in the board.
+    pub hidden: bool,
+    /// Part of the pixels shown as `[x, y, width, height]`, all of them
+    /// when None. See `crop`.
+    pub crop: Option<[u32; 4]>,
+    /// Color changes applied when drawing.
+    pub adjustments: Adjustments,
+    /// Dominant colors, shown under the image. See `palette`.
+    pub palette: Vec<[u8; 4]>,
 }
 
 impl Image {
-    pub fn new(position: [f32; 2], bytes: Vec<u8>) -> Self {
-        let image = image::load_from_memory(&bytes).unwrap();
+    pub fn new(position: [f32; 2], bytes: Vec<u8>) -> image::ImageResult<Self> {
+        let metadata = Metadata::read(&bytes);
+        // Decoders ignore the EXIF orientation, bake it into the pixels so
+        // photos come up the way other viewers show them.
+        let image =
+            metadata::apply_orientation(image::load_from_memory(&bytes)?, metadata.orientation);
+
+        Ok(Self {
+            position,
+            perceptual_hash: Some(similar::perceptual_hash(&image)),
+            image: Arc::new(image),
+            source: None,
+            hash: content_hash(&bytes),
+            metadata,
+            tags: Vec::new(),
+            note: String::new(),
+            storage: Storage::default(),
+            missing: None,
+            locked: false,
+            hidden: false,
+            crop: None,
+            adjustments: Adjustments::default(),
+            palette: Vec::new(),
+        })
+    }
 
-        Self { position, image }
+    /// Stand-in for a linked image whose file is missing.
+    pub fn placeholder(position: [f32; 2], size: [u32; 2], source: Source, hash: String) -> Self {
+        let checker = image::RgbaImage::from_fn(8, 8, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([200, 0, 200, 255])
+            } else {
+                image::Rgba([60, 60, 60, 255])
+            }
+        });
+
+        Self {
+            position,
+            image: Arc::new(DynamicImage::ImageRgba8(checker)),
+            source: Some(source),
+            hash,
+            perceptual_hash: None,
+            metadata: Metadata::default(),
+            tags: Vec::new(),
+            note: String::new(),
+            storage: Storage::Linked,
+            missing: Some(size),
+            locked: false,
+            hidden: false,
+            crop: None,
+            adjustments: Adjustments::default(),
+            palette: Vec::new(),
+        }
+    }
+
+    /// How the image is saved, for listings.
+    pub fn storage_label(&self) -> &'static str {
+        match (self.storage, self.missing) {
+            (Storage::Embedded, _) => "embedded",
+            (Storage::Linked, None) => "linked",
+            (Storage::Linked, Some(_)) => "missing",
+        }
+    }
+
+    /// Identifies the pixels of the image, images with the same key share
+    /// them and their GPU texture.
+    pub fn content_key(&self) -> &str {
+        match self.missing {
+            Some(_) => PLACEHOLDER_KEY,
+            None => &self.hash,
+        }
+    }
+
+    /// Size of the image on the board, in board units, once cropped.
+    pub fn size(&self) -> [f32; 2] {
+        let [_, _, width, height] = self.crop_rect();
+        [width as f32, height as f32]
     }
 }
 
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Default)]
 pub struct Library {
     images: HashMap<uuid::Uuid, Image>,
+    /// Labels and notes, drawn over the images.
+    texts: HashMap<uuid::Uuid, Text>,
+    /// Solid color chips, drawn with the images.
+    chips: HashMap<uuid::Uuid, Chip>,
+    /// Arrows, boxes and strokes marking up the images.
+    annotations: HashMap<uuid::Uuid, Annotation>,
+    /// Named regions grouping the items laid out in them.
+    frames: HashMap<uuid::Uuid, Frame>,
+    /// Items moved, scaled and rotated together, possibly nested.
+    groups: HashMap<uuid::Uuid, Group>,
+    /// Decoded pixels by content key, so identical imports are held once.
+    contents: HashMap<String, Weak<DynamicImage>>,
+    index: search::Index,
+    /// Folders whose new images are imported automatically.
+    pub watch_folders: Vec<PathBuf>,
+    /// Colors picked from the images, oldest first.
+    pub swatches: Vec<[u8; 4]>,
 }
 
 impl Library {
     pub fn new() -> Self {
         let images = HashMap::new();
-        Self { images }
+        Self {
+            images,
+            texts: HashMap::new(),
+            chips: HashMap::new(),
+            annotations: HashMap::new(),
+            frames: HashMap::new(),
+            groups: HashMap::new(),
+            contents: HashMap::new(),
+            index: search::Index::default(),
+            watch_folders: Vec::new(),
+            swatches: Vec::new(),
+        }
     }
 
-    pub fn insert(&mut self, image: Image) -> Option<uuid::Uuid> {
+    /// Makes `image` use the pixels already in the library for its content,
+    /// or registers its own.
+    fn intern(&mut self, image: &mut Image) {
+        let key = image.content_key().to_string();
+        match self.contents.get(&key).and_then(Weak::upgrade) {
+            Some(pixels) => image.image = pixels,
+            None => {
+                self.contents.insert(key, Arc::downgrade(&image.image));
+            }
+        }
+    }
+
+    pub fn insert(&mut self, mut image: Image) -> Option<uuid::Uuid> {
+        self.intern(&mut image);
         let key = uuid::Uuid::new_v4();
-        println!("{:?}", image.position);
+        log::debug!("{:?}", image.position);
+        self.index.update(key, &image);
         let maybe_value = self.images.insert(key, image);
         match maybe_value {
             Some(value) => {
-                println!("Key already exists ! rollback to old value");
+                log::warn!("Key already exists ! rollback to old value");
                 self.images.insert(key, value);
-                return Some(key);
+                Some(key)
             }
             None => {
-                println!("New image added to Library");
-                return Some(key);
+                log::debug!("New image added to Library");
+                Some(key)
             }
+        }
+    }
+
+    /// Inserts an image under a known key, e.g. when loading a saved board.
+    pub fn insert_with_key(&mut self, key: uuid::Uuid, mut image: Image) {
+        self.intern(&mut image);
+        self.index.update(key, &image);
+        self.images.insert(key, image);
+    }
+
+    /// Changes the image `key` through `edit`, for edits the search index
+    /// must see such as tags, note or metadata.
+    pub fn update(&mut self, key: &uuid::Uuid, edit: impl FnOnce(&mut Image)) {
+        if let Some(image) = self.images.get_mut(key) {
+            edit(image);
+            self.index.update(*key, image);
+        }
+    }
+
+    /// Images matching every word of `query`, see `search::Index::search`.
+    pub fn search(&self, query: &str) -> Vec<uuid::Uuid> {
+        self.index.search(query)
+    }
+
+    /// Swaps the pixels of an image for the ones of `reloaded`, a new decode
+    /// of its file, keeping where it sits on the board.
+    pub fn reload(&mut self, key: &uuid::Uuid, reloaded: &Image) {
+        let Some(mut image) = self.images.remove(key) else {
+            return;
+        };
+        let previous = image.content_key().to_string();
+        image.image = reloaded.image.clone();
+        image.hash = reloaded.hash.clone();
+        image.perceptual_hash = reloaded.perceptual_hash;
+        image.metadata = Metadata {
+            provenance: image.metadata.provenance,
+            ..reloaded.metadata.clone()
         };
+        image.source = reloaded.source.clone();
+        image.missing = None;
+        // The new pixels may be smaller.
+        image.crop = image
+            .crop
+            .and_then(|rect| crop::clamp(rect, image.full_size()));
+        self.insert_with_key(*key, image);
+        self.release(&previous);
+    }
+
+    /// Forgets the content `content_key` once no image shows it anymore.
+    fn release(&mut self, content_key: &str) {
+        if !self
+            .images
+            .values()
+            .any(|image| image.content_key() == content_key)
+        {
+            self.contents.remove(content_key);
+        }
+    }
+
+    /// Moves every image of `other` into this library, keeping their keys.
+    pub fn extend(&mut self, other: Library) {
+        for (key, image) in other.images {
+            self.insert_with_key(key, image);
+        }
+        self.texts.extend(other.texts);
+        self.chips.extend(other.chips);
+        self.annotations.extend(other.annotations);
+        self.frames.extend(other.frames);
+        self.groups.extend(other.groups);
+        for color in other.swatches {
+            self.add_swatch(color);
+        }
+        for folder in other.watch_folders {
+            if !self.watch_folders.contains(&folder) {
+                self.watch_folders.push(folder);
+            }
+        }
+    }
+
+    /// Free spot right of everything on the board, where appended images go.
+    pub fn next_position(&self) -> [f32; 2] {
+        match self.bounds() {
+            Some([x, y, width, _]) => [x + width + SPACING, y],
+            None => [0., 0.],
+        }
     }
 
     pub fn get(&self, key: &uuid::Uuid) -> Option<&Image> {
         self.images.get(key)
     }
+
+    pub fn get_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Image> {
+        self.images.get_mut(key)
+    }
+
+    pub fn insert_text(&mut self, text: Text) -> uuid::Uuid {
+        let key = uuid::Uuid::new_v4();
+        self.texts.insert(key, text);
+        key
+    }
+
+    pub fn insert_text_with_key(&mut self, key: uuid::Uuid, text: Text) {
+        self.texts.insert(key, text);
+    }
+
+    pub fn get_text(&self, key: &uuid::Uuid) -> Option<&Text> {
+        self.texts.get(key)
+    }
+
+    pub fn get_text_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Text> {
+        self.texts.get_mut(key)
+    }
+
+    pub fn texts(&self) -> impl Iterator<Item = (&uuid::Uuid, &Text)> {
+        self.texts.iter()
+    }
+
+    pub fn insert_chip(&mut self, chip: Chip) -> uuid::Uuid {
+        let key = uuid::Uuid::new_v4();
+        self.chips.insert(key, chip);
+        key
+    }
+
+    pub fn insert_chip_with_key(&mut self, key: uuid::Uuid, chip: Chip) {
+        self.chips.insert(key, chip);
+    }
+
+    pub fn get_chip(&self, key: &uuid::Uuid) -> Option<&Chip> {
+        self.chips.get(key)
+    }
+
+    pub fn get_chip_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Chip> {
+        self.chips.get_mut(key)
+    }
+
+    pub fn chips(&self) -> impl Iterator<Item = (&uuid::Uuid, &Chip)> {
+        self.chips.iter()
+    }
+
+    pub fn insert_annotation(&mut self, annotation: Annotation) -> uuid::Uuid {
+        let key = uuid::Uuid::new_v4();
+        self.annotations.insert(key, annotation);
+        key
+    }
+
+    pub fn insert_annotation_with_key(&mut self, key: uuid::Uuid, annotation: Annotation) {
+        self.annotations.insert(key, annotation);
+    }
+
+    pub fn remove_annotation(&mut self, key: &uuid::Uuid) -> Option<Annotation> {
+        self.annotations.remove(key)
+    }
+
+    pub fn get_annotation(&self, key: &uuid::Uuid) -> Option<&Annotation> {
+        self.annotations.get(key)
+    }
+
+    pub fn get_annotation_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Annotation> {
+        self.annotations.get_mut(key)
+    }
+
+    pub fn annotations(&self) -> impl Iterator<Item = (&uuid::Uuid, &Annotation)> {
+        self.annotations.iter()
+    }
+
+    /// Annotations that move with the image `key`.
+    pub fn annotations_on(&self, key: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        self.annotations
+            .iter()
+            .filter(|(_, annotation)| annotation.attached_to == Some(*key))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Position the points of `annotation` are relative to, in the
+    /// coordinates `world` places on the board.
+    pub fn annotation_origin(&self, annotation: &Annotation) -> [f32; 2] {
+        annotation
+            .attached_to
+            .and_then(|key| self.images.get(&key))
+            .map_or([0., 0.], |image| image.position)
+    }
+
+    pub fn insert_frame(&mut self, frame: Frame) -> uuid::Uuid {
+        let key = uuid::Uuid::new_v4();
+        self.frames.insert(key, frame);
+        key
+    }
+
+    pub fn insert_frame_with_key(&mut self, key: uuid::Uuid, frame: Frame) {
+        self.frames.insert(key, frame);
+    }
+
+    /// Removes a frame, its contents stay on the board.
+    pub fn remove_frame(&mut self, key: &uuid::Uuid) -> Option<Frame> {
+        self.frames.remove(key)
+    }
+
+    pub fn get_frame(&self, key: &uuid::Uuid) -> Option<&Frame> {
+        self.frames.get(key)
+    }
+
+    pub fn get_frame_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Frame> {
+        self.frames.get_mut(key)
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = (&uuid::Uuid, &Frame)> {
+        self.frames.iter()
+    }
+
+    pub fn frames_mut(&mut self) -> impl Iterator<Item = (&uuid::Uuid, &mut Frame)> {
+        self.frames.iter_mut()
+    }
+
+    /// Frame holding `item`, if any.
+    pub fn frame_of(&self, item: &uuid::Uuid) -> Option<uuid::Uuid> {
+        self.frames
+            .iter()
+            .find(|(_, frame)| frame.contents.contains(item))
+            .map(|(key, _)| *key)
+    }
+
+    pub fn insert_group_with_key(&mut self, key: uuid::Uuid, group: Group) {
+        self.groups.insert(key, group);
+    }
+
+    /// Removes a group and nothing else, see `group::ungroup` to keep its
+    /// children in place.
+    pub fn remove_group(&mut self, key: &uuid::Uuid) -> Option<Group> {
+        self.groups.remove(key)
+    }
+
+    pub fn get_group(&self, key: &uuid::Uuid) -> Option<&Group> {
+        self.groups.get(key)
+    }
+
+    pub fn get_group_mut(&mut self, key: &uuid::Uuid) -> Option<&mut Group> {
+        self.groups.get_mut(key)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&uuid::Uuid, &Group)> {
+        self.groups.iter()
+    }
+
+    /// Group holding `key`, if any.
+    pub fn parent_of(&self, key: &uuid::Uuid) -> Option<uuid::Uuid> {
+        self.groups
+            .iter()
+            .find(|(_, group)| group.children.contains(key))
+            .map(|(parent, _)| *parent)
+    }
+
+    /// Outermost group holding `key`, or `key` itself.
+    pub fn root_of(&self, key: &uuid::Uuid) -> uuid::Uuid {
+        let mut root = *key;
+        while let Some(parent) = self.parent_of(&root) {
+            root = parent;
+        }
+        root
+    }
+
+    /// Transform from the coordinates of `key` to the board: the transforms
+    /// of the groups holding it, composed. Annotations on an image use the
+    /// one of the image.
+    pub fn world(&self, key: &uuid::Uuid) -> Transform {
+        let mut current = self
+            .annotations
+            .get(key)
+            .and_then(|annotation| annotation.attached_to)
+            .unwrap_or(*key);
+        let mut world = Transform::IDENTITY;
+        while let Some(parent) = self.parent_of(&current) {
+            world = self.groups[&parent].transform.then(&world);
+            current = parent;
+        }
+        world
+    }
+
+    /// The board point `position` in the coordinates of `key`.
+    pub fn to_local(&self, key: &uuid::Uuid, position: [f32; 2]) -> [f32; 2] {
+        self.world(key)
+            .inverse()
+            .map_or(position, |inverse| inverse.apply(position))
+    }
+
+    /// What frames can hold: images, texts, chips and the annotations
+    /// attached to no image.
+    pub fn items(&self) -> impl Iterator<Item = uuid::Uuid> + '_ {
+        let annotations = self
+            .annotations
+            .iter()
+            .filter(|(_, annotation)| annotation.attached_to.is_none())
+            .map(|(key, _)| *key);
+        self.images
+            .keys()
+            .chain(self.texts.keys())
+            .chain(self.chips.keys())
+            .copied()
+            .chain(annotations)
+    }
+
+    /// Items not drawn: hidden images, the contents of collapsed frames and
+    /// the annotations on them.
+    pub fn hidden(&self) -> HashSet<uuid::Uuid> {
+        self.hidden_except(|_| true)
+    }
+
+    /// Items not drawn as `hidden` says, the images `shown` leaves out and
+    /// the annotations on them too.
+    pub fn hidden_except(&self, shown: impl Fn(&uuid::Uuid) -> bool) -> HashSet<uuid::Uuid> {
+        let mut hidden: HashSet<uuid::Uuid> = self
+            .frames
+            .values()
+            .filter(|frame| frame.collapsed)
+            .flat_map(|frame| frame.contents.iter().copied())
+            .collect();
+        hidden.extend(
+            self.images
+                .iter()
+                .filter(|(key, image)| image.hidden || !shown(key))
+                .map(|(key, _)| *key),
+        );
+        let on_hidden: Vec<uuid::Uuid> = self
+            .annotations
+            .iter()
+            .filter(|(_, annotation)| {
+                annotation
+                    .attached_to
+                    .is_some_and(|image| hidden.contains(&image))
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        hidden.extend(on_hidden);
+        hidden
+    }
+
+    /// Linked images whose file could not be found.
+    pub fn missing(&self) -> Vec<uuid::Uuid> {
+        self.images
+            .iter()
+            .filter(|(_, image)| image.missing.is_some())
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Text, chip, image, annotation or frame under the board point
+    /// `position`, texts first as they are drawn on top. Hidden items and
+    /// locked images are left out, and an item in a group gives its
+    /// outermost group.
+    pub fn hit_test(&self, position: [f32; 2]) -> Option<uuid::Uuid> {
+        let hidden = self.hidden();
+        let under = |key: &&uuid::Uuid| !hidden.contains(*key) && self.covers(key, position);
+        let text = self.texts.keys().find(under).copied();
+        let chip = self.chips.keys().find(under).copied();
+        let mut annotations = self
+            .annotations
+            .iter()
+            .filter(|(_, annotation)| annotation.attached_to.is_none())
+            .map(|(key, _)| key);
+        let image = self
+            .images
+            .iter()
+            .filter(|(_, image)| !image.locked)
+            .map(|(key, _)| key)
+            .find(under)
+            .copied();
+        let item = text
+            .or(chip)
+            .or(image)
+            .or_else(|| annotations.find(under).copied())
+            .map(|key| self.root_of(&key));
+        item.or_else(|| frame::title_at(self, position))
+            .or_else(|| frame::frame_at(self, position))
+    }
+
+    /// Adds `color` to the swatches, unless it is already there.
+    pub fn add_swatch(&mut self, color: [u8; 4]) -> bool {
+        let new = !self.swatches.contains(&color);
+        if new {
+            self.swatches.push(color);
+        }
+        new
+    }
+
+    /// Visible image under the board point `position`.
+    pub fn image_at(&self, position: [f32; 2]) -> Option<uuid::Uuid> {
+        let hidden = self.hidden();
+        self.images
+            .keys()
+            .find(|key| !hidden.contains(key) && self.covers(key, position))
+            .copied()
+    }
+
+    /// Whether the image, text or annotation `key` covers the board point
+    /// `position`, however its groups turn it.
+    fn covers(&self, key: &uuid::Uuid, position: [f32; 2]) -> bool {
+        self.local_rect(key)
+            .is_some_and(|(origin, size)| self.world(key).covers(origin, size, position))
+    }
+
+    /// Images whose imported bytes hash to `hash`.
+    pub fn find_by_hash(&self, hash: &str) -> Vec<uuid::Uuid> {
+        self.images
+            .iter()
+            .filter(|(_, image)| image.missing.is_none() && image.hash == hash)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Images imported from the file at `path`.
+    pub fn find_by_source(&self, path: &Path) -> Vec<uuid::Uuid> {
+        self.images
+            .iter()
+            .filter(|(_, image)| {
+                image
+                    .source
+                    .as_ref()
+                    .is_some_and(|source| source.path == path)
+            })
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &uuid::Uuid> {
+        self.images.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&uuid::Uuid, &Image)> {
+        self.images.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Bounding box `[x, y, width, height]` of everything shown on the board.
+    pub fn bounds(&self) -> Option<[f32; 4]> {
+        let hidden = self.hidden();
+        let items = self
+            .images
+            .keys()
+            .chain(self.texts.keys())
+            .chain(self.chips.keys())
+            .chain(self.annotations.keys())
+            .filter(|key| !hidden.contains(key))
+            .filter_map(|key| self.rect(key));
+        let palettes = self
+            .images
+            .iter()
+            .filter(|(key, _)| !hidden.contains(key))
+            .filter_map(|(key, image)| {
+                let (position, size) = image.palette_rect()?;
+                Some(self.world(key).bounds(position, size))
+            });
+        let frames = self.frames.values().map(Frame::rect);
+        bounds(items.chain(palettes).chain(frames))
+    }
+
+    /// Bounding box of the images, texts, annotations, frames and groups
+    /// `keys`.
+    pub fn bounds_of(&self, keys: &[uuid::Uuid]) -> Option<[f32; 4]> {
+        bounds(keys.iter().filter_map(|key| self.rect(key)))
+    }
+
+    /// Box around the image, text, annotation, frame or group `key` on the
+    /// board.
+    pub fn rect(&self, key: &uuid::Uuid) -> Option<([f32; 2], [f32; 2])> {
+        if let Some(frame) = self.frames.get(key) {
+            return Some(frame.rect());
+        }
+        if self.groups.contains_key(key) {
+            let members = group::members(self, key);
+            let attached = members
+                .iter()
+                .flat_map(|member| self.annotations_on(member));
+            let [x, y, width, height] = bounds(
+                members
+                    .iter()
+                    .copied()
+                    .chain(attached)
+                    .filter_map(|member| self.rect(&member)),
+            )?;
+            return Some(([x, y], [width, height]));
+        }
+        let (position, size) = self.local_rect(key)?;
+        Some(self.world(key).bounds(position, size))
+    }
+
+    /// Position and size of the image, text or annotation `key` in its own
+    /// coordinates, before its groups place it on the board.
+    pub fn local_rect(&self, key: &uuid::Uuid) -> Option<([f32; 2], [f32; 2])> {
+        if let Some(image) = self.images.get(key) {
+            return Some((image.position, image.size()));
+        }
+        if let Some(text) = self.texts.get(key) {
+            return Some((text.position, text.size()));
+        }
+        if let Some(chip) = self.chips.get(key) {
+            return Some((chip.position, chip.size()));
+        }
+        self.annotations
+            .get(key)
+            .map(|annotation| annotation.rect(self.annotation_origin(annotation)))
+    }
+}
+
+/// Box `[x, y, width, height]` around every `(position, size)` rectangle.
+fn bounds(rects: impl Iterator<Item = ([f32; 2], [f32; 2])>) -> Option<[f32; 4]> {
+    rects
+        .map(|(position, size)| {
+            [
+                position[0],
+                position[1],
+                position[0] + size[0],
+                position[1] + size[1],
+            ]
+        })
+        .reduce(|a, b| {
+            [
+                a[0].min(b[0]),
+                a[1].min(b[1]),
+                a[2].max(b[2]),
+                a[3].max(b[3]),
+            ]
+        })
+        .map(|[left, top, right, bottom]| [left, top, right - left, bottom - top])
+}
+
+/// `pixels` encoded as a PNG file, for tests to import.
+#[cfg(test)]
+pub(crate) fn png(pixels: image::RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgba8(pixels)
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageOutputFormat::Png,
+        )
+        .expect("PNG encoding to memory");
+    bytes
 }
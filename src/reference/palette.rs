@@ -0,0 +1,378 @@
+//! Dominant colors of an image, found by k-means in the Oklab space, and the
+//! palette files they export to.
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use image::{DynamicImage, GenericImageView};
+
+use super::{color, Image, Library};
+
+/// Colors extracted when no count is given, and the most asked for.
+pub const DEFAULT_COLORS: usize = 6;
+pub const MAX_COLORS: usize = 16;
+
+/// Pixels read at most, the others are skipped evenly.
+const SAMPLES: u32 = 128 * 128;
+/// Pixels less opaque than this are left out.
+const MIN_ALPHA: u8 = 128;
+const ITERATIONS: usize = 16;
+
+/// Tallest swatch under an image, in board units, smaller ones stay square.
+const SWATCH_HEIGHT: f32 = 32.;
+/// Gap between the image and its swatches, as a part of their height.
+const SWATCH_GAP: f32 = 0.25;
+
+/// Pixels of the same 5 bits a channel, counted once with their mean color.
+struct Bin {
+    count: f32,
+    color: [u8; 4],
+    lab: [f32; 3],
+}
+
+/// Up to `count` dominant colors of the `[x, y, width, height]` part of
+/// `image`, the most covering first.
+pub fn extract(image: &DynamicImage, rect: [u32; 4], count: usize) -> Vec<[u8; 4]> {
+    let bins = bins(image, rect);
+    let count = count.clamp(1, MAX_COLORS).min(bins.len());
+    if count == 0 {
+        return Vec::new();
+    }
+
+    // Seeds are spread by weighted farthest point, which k-means++ does at
+    // random, so a palette comes out the same every time.
+    let mut centers = vec![bins
+        .iter()
+        .max_by(|a, b| a.count.total_cmp(&b.count))
+        .map(|bin| bin.lab)
+        .unwrap_or_default()];
+    while centers.len() < count {
+        let farthest = bins.iter().max_by(|a, b| {
+            let weight = |bin: &Bin| bin.count * nearest(&centers, bin.lab).1;
+            weight(a).total_cmp(&weight(b))
+        });
+        match farthest {
+            Some(bin) if nearest(&centers, bin.lab).1 > 0. => centers.push(bin.lab),
+            _ => break,
+        }
+    }
+
+    let mut members = vec![0; bins.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![([0f32; 3], 0f32); centers.len()];
+        let mut moved = false;
+        for (member, bin) in members.iter_mut().zip(&bins) {
+            let (index, _) = nearest(&centers, bin.lab);
+            moved |= *member != index;
+            *member = index;
+            let (sum, weight) = &mut sums[index];
+            for (sum, value) in sum.iter_mut().zip(bin.lab) {
+                *sum += value * bin.count;
+            }
+            *weight += bin.count;
+        }
+        for (center, (sum, weight)) in centers.iter_mut().zip(sums) {
+            if weight > 0. {
+                *center = sum.map(|value| value / weight);
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    // Each center shows as the pixel color closest to it, one the image
+    // really has.
+    let mut clusters: Vec<(f32, [u8; 4])> = centers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, center)| {
+            let weight: f32 = bins
+                .iter()
+                .zip(&members)
+                .filter(|(_, member)| **member == index)
+                .map(|(bin, _)| bin.count)
+                .sum();
+            let closest = bins
+                .iter()
+                .min_by(|a, b| distance(a.lab, *center).total_cmp(&distance(b.lab, *center)))?;
+            (weight > 0.).then_some((weight, closest.color))
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut colors: Vec<[u8; 4]> = Vec::with_capacity(clusters.len());
+    for (_, color) in clusters {
+        if !colors.contains(&color) {
+            colors.push(color);
+        }
+    }
+    colors
+}
+
+impl Image {
+    /// Position and size of the row of swatches under the image, in its own
+    /// coordinates. None without a palette.
+    pub fn palette_rect(&self) -> Option<([f32; 2], [f32; 2])> {
+        if self.palette.is_empty() {
+            return None;
+        }
+        let [x, y] = self.position;
+        let [width, height] = self.size();
+        let side = (width / self.palette.len() as f32).min(SWATCH_HEIGHT);
+        Some(([x, y + height + side * SWATCH_GAP], [width, side]))
+    }
+}
+
+/// Extracts the palettes of the images `keys`, skipping the missing ones.
+/// Returns how many got one.
+pub fn extract_images(library: &mut Library, keys: &[uuid::Uuid], count: usize) -> usize {
+    let mut extracted = 0;
+    for key in keys {
+        let Some(image) = library.get(key).filter(|image| image.missing.is_none()) else {
+            continue;
+        };
+        let palette = extract(&image.image, image.crop_rect(), count);
+        if let Some(image) = library.get_mut(key) {
+            image.palette = palette;
+            extracted += 1;
+        }
+    }
+    extracted
+}
+
+fn bins(image: &DynamicImage, [x, y, width, height]: [u32; 4]) -> Vec<Bin> {
+    let step = ((width as f32 * height as f32 / SAMPLES as f32)
+        .sqrt()
+        .ceil() as u32)
+        .max(1);
+    let mut sums: HashMap<[u8; 3], ([u32; 3], u32)> = HashMap::new();
+    for py in (y..y + height).step_by(step as usize) {
+        for px in (x..x + width).step_by(step as usize) {
+            let [r, g, b, a] = image.get_pixel(px, py).0;
+            if a < MIN_ALPHA {
+                continue;
+            }
+            let (sum, count) = sums.entry([r >> 3, g >> 3, b >> 3]).or_default();
+            for (sum, channel) in sum.iter_mut().zip([r, g, b]) {
+                *sum += channel as u32;
+            }
+            *count += 1;
+        }
+    }
+    let mut bins: Vec<Bin> = sums
+        .into_values()
+        .map(|(sum, count)| {
+            let [r, g, b] = sum.map(|sum| (sum / count) as u8);
+            let color = [r, g, b, 255];
+            Bin {
+                count: count as f32,
+                color,
+                lab: color::oklab(color),
+            }
+        })
+        .collect();
+    // Hash map order changes between runs, ties must not.
+    bins.sort_by_key(|bin| bin.color);
+    bins
+}
+
+/// Index of the center nearest to `lab`, and the squared distance to it.
+fn nearest(centers: &[[f32; 3]], lab: [f32; 3]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| distance(*center, lab))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.))
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// File formats a palette exports to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// GIMP palette, `.gpl`.
+    Gimp,
+    /// Adobe Swatch Exchange, `.ase`.
+    Ase,
+    /// JSON array of hex strings, `.json`.
+    Json,
+    /// One hex color a line, any other extension.
+    Hex,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "gpl" => Format::Gimp,
+            "ase" => Format::Ase,
+            "json" => Format::Json,
+            _ => Format::Hex,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Gimp => "GIMP palette",
+            Format::Ase => "Adobe Swatch Exchange",
+            Format::Json => "JSON",
+            Format::Hex => "hex list",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Writes `colors` to `path` in the format its extension names, under the
+/// palette name `name`.
+pub fn export(path: &Path, name: &str, colors: &[[u8; 4]]) -> io::Result<Format> {
+    let format = Format::from_path(path);
+    let bytes = match format {
+        Format::Gimp => gimp(name, colors).into_bytes(),
+        Format::Ase => ase(colors),
+        Format::Json => {
+            let hexes: Vec<String> = colors.iter().map(|c| color::hex(*c)).collect();
+            serde_json::to_vec_pretty(&hexes)?
+        }
+        Format::Hex => colors
+            .iter()
+            .map(|c| color::hex(*c) + "\n")
+            .collect::<String>()
+            .into_bytes(),
+    };
+    fs::write(path, bytes)?;
+    Ok(format)
+}
+
+fn gimp(name: &str, colors: &[[u8; 4]]) -> String {
+    let mut text = format!(
+        "GIMP Palette\nName: {}\nColumns: {}\n#\n",
+        name,
+        colors.len()
+    );
+    for color in colors {
+        let [r, g, b, _] = *color;
+        text += &format!("{:3} {:3} {:3}\t{}\n", r, g, b, color::hex(*color));
+    }
+    text
+}
+
+/// Version 1.0 of the format, one global RGB color block a color named
+/// after its hex. Big endian throughout.
+fn ase(colors: &[[u8; 4]]) -> Vec<u8> {
+    let mut bytes = b"ASEF".to_vec();
+    bytes.extend(1u16.to_be_bytes());
+    bytes.extend(0u16.to_be_bytes());
+    bytes.extend((colors.len() as u32).to_be_bytes());
+    for color in colors {
+        let [r, g, b, _] = *color;
+        let name: Vec<u16> = color::hex(*color).encode_utf16().chain([0]).collect();
+        let mut block = (name.len() as u16).to_be_bytes().to_vec();
+        block.extend(name.iter().flat_map(|unit| unit.to_be_bytes()));
+        block.extend(b"RGB ");
+        for channel in [r, g, b] {
+            block.extend((channel as f32 / 255.).to_be_bytes());
+        }
+        // Global color.
+        block.extend(0u16.to_be_bytes());
+
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend((block.len() as u32).to_be_bytes());
+        bytes.extend(block);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    const ORANGE: [u8; 4] = [255, 136, 0, 255];
+    const TEAL: [u8; 4] = [0, 128, 128, 255];
+
+    /// Orange over the left three quarters, teal over the rest.
+    fn two_colors() -> DynamicImage {
+        let pixels = RgbaImage::from_fn(40, 10, |x, _| Rgba(if x < 30 { ORANGE } else { TEAL }));
+        DynamicImage::ImageRgba8(pixels)
+    }
+
+    /// Path under the system temporary folder, unique to the test.
+    fn scratch(extension: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rustyref-{}.{}", uuid::Uuid::new_v4(), extension))
+    }
+
+    #[test]
+    fn extract_puts_the_most_covering_first() {
+        let image = two_colors();
+        assert_eq!(extract(&image, [0, 0, 40, 10], 2), [ORANGE, TEAL]);
+        // No more colors than the image has.
+        assert_eq!(extract(&image, [0, 0, 40, 10], 6), [ORANGE, TEAL]);
+        assert_eq!(extract(&image, [30, 0, 10, 10], 4), [TEAL]);
+    }
+
+    #[test]
+    fn export_writes_the_format_of_the_extension() {
+        let colors = [ORANGE, [0, 128, 128, 64]];
+
+        let path = scratch("gpl");
+        assert_eq!(export(&path, "board", &colors).unwrap(), Format::Gimp);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "GIMP Palette\nName: board\nColumns: 2\n#\n\
+             255 136   0\t#ff8800\n  0 128 128\t#00808040\n"
+        );
+        fs::remove_file(path).unwrap();
+
+        let path = scratch("json");
+        assert_eq!(export(&path, "board", &colors).unwrap(), Format::Json);
+        let hexes: Vec<String> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(hexes, ["#ff8800", "#00808040"]);
+        fs::remove_file(path).unwrap();
+
+        let path = scratch("txt");
+        assert_eq!(export(&path, "board", &colors).unwrap(), Format::Hex);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#ff8800\n#00808040\n");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ase_holds_one_rgb_block_a_color() {
+        let path = scratch("ASE");
+        assert_eq!(export(&path, "board", &[ORANGE]).unwrap(), Format::Ase);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(&bytes[..4], b"ASEF");
+        assert_eq!(bytes[4..8], [0, 1, 0, 0]);
+        assert_eq!(bytes[8..12], 1u32.to_be_bytes());
+        // Color entry, then the length of what follows.
+        assert_eq!(bytes[12..14], [0, 1]);
+        let length = u32::from_be_bytes(bytes[14..18].try_into().unwrap()) as usize;
+        let block = &bytes[18..];
+        assert_eq!(block.len(), length);
+
+        // "#ff8800" and its terminating zero, in UTF-16.
+        assert_eq!(block[..2], 8u16.to_be_bytes());
+        let name: Vec<u16> = block[2..18]
+            .chunks(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        assert_eq!(String::from_utf16(&name).unwrap(), "#ff8800\0");
+        assert_eq!(&block[18..22], b"RGB ");
+        let channels: Vec<f32> = block[22..34]
+            .chunks(4)
+            .map(|value| f32::from_be_bytes(value.try_into().unwrap()))
+            .collect();
+        assert_eq!(channels, [1., 136. / 255., 0.]);
+        assert_eq!(block[34..], [0, 0]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{board, content_hash, import, Image, Library, Source, Storage};
+
+/// Name of the folder `collect_assets` copies linked files to.
+pub const ASSETS_FOLDER: &str = "assets";
+
+/// Looks for the missing linked files of `library` in `folder` and its
+/// subfolders, by file name first and by content when the file was renamed.
+/// Returns the images that were found and reloaded.
+pub fn relink(library: &mut Library, folder: &Path) -> io::Result<Vec<uuid::Uuid>> {
+    let missing = library.missing();
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut candidates = Vec::new();
+    image_files(folder, &mut candidates)?;
+    let mut hashes: HashMap<PathBuf, String> = HashMap::new();
+    let mut hash_of = |path: &Path| -> Option<String> {
+        if let Some(hash) = hashes.get(path) {
+            return Some(hash.clone());
+        }
+        let hash = content_hash(&fs::read(path).ok()?);
+        hashes.insert(path.to_path_buf(), hash.clone());
+        Some(hash)
+    };
+
+    let mut relinked = Vec::new();
+    for image_id in missing {
+        let Some(image) = library.get(&image_id) else {
+            continue;
+        };
+        let file_name = image
+            .source
+            .as_ref()
+            .and_then(|source| source.path.file_name())
+            .map(|name| name.to_os_string());
+        let hash = image.hash.clone();
+
+        let same_name: Vec<&PathBuf> = candidates
+            .iter()
+            .filter(|path| path.file_name().map(|name| name.to_os_string()) == file_name)
+            .collect();
+        // Without a recorded hash the name alone has to do.
+        let found = same_name
+            .iter()
+            .find(|path| hash.is_empty() || hash_of(path).as_deref() == Some(&hash))
+            .copied()
+            .or_else(|| {
+                if hash.is_empty() {
+                    return None;
+                }
+                candidates
+                    .iter()
+                    .find(|path| hash_of(path).as_deref() == Some(&hash))
+            })
+            .cloned();
+
+        if let Some(path) = found {
+            // Only the pixels and source change, the rest of the image stays.
+            match Image::open(&path, image.position) {
+                Ok(found) => {
+                    library.reload(&image_id, &found);
+                    relinked.push(image_id);
+                }
+                Err(e) => log::warn!("{}: {}", path.display(), e),
+            }
+        }
+    }
+
+    Ok(relinked)
+}
+
+fn image_files(folder: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            image_files(&path, files)?;
+        } else if import::is_image_path(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Copies the files of every linked image into an `assets` folder next to the
+/// board at `board_path` and links the images to the copies. Returns how many
+/// files were copied.
+pub fn collect_assets(library: &mut Library, board_path: &Path) -> io::Result<usize> {
+    let assets = board::board_directory(board_path)?.join(ASSETS_FOLDER);
+    fs::create_dir_all(&assets)?;
+
+    let keys: Vec<uuid::Uuid> = library.keys().copied().collect();
+    let mut copied = 0;
+    for key in keys {
+        let Some(image) = library.get_mut(&key) else {
+            continue;
+        };
+        let Some(source) = &image.source else {
+            continue;
+        };
+        if image.storage != Storage::Linked
+            || image.missing.is_some()
+            || source.path.starts_with(&assets)
+        {
+            continue;
+        }
+
+        let destination = free_destination(&assets, &source.path, &image.hash)?;
+        if !destination.exists() {
+            fs::copy(&source.path, &destination)?;
+            copied += 1;
+        }
+        image.source = Some(Source {
+            modified: import::modified(&destination),
+            path: destination,
+        });
+    }
+
+    Ok(copied)
+}
+
+/// Path in `assets` for a copy of `path`: its own name, or a numbered variant
+/// when another file already took it. An identical file is reused.
+fn free_destination(assets: &Path, path: &Path, hash: &str) -> io::Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    for index in 0.. {
+        let name = match index {
+            0 => format!("{}{}", stem, extension),
+            _ => format!("{}-{}{}", stem, index, extension),
+        };
+        let destination = assets.join(name);
+        if !destination.exists() || content_hash(&fs::read(&destination)?) == hash {
+            return Ok(destination);
+        }
+    }
+    unreachable!("ran out of file names")
+}
@@ -0,0 +1,126 @@
+//! Word index over the text attached to images: tags, note, file name and
+//! metadata. `Library` keeps it up to date as images are added and edited.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::Image;
+
+#[derive(Default)]
+pub struct Index {
+    /// Images by the words their text contains.
+    words: BTreeMap<String, HashSet<uuid::Uuid>>,
+    /// Words indexed for every image, to take them out again.
+    indexed: HashMap<uuid::Uuid, Vec<String>>,
+}
+
+impl Index {
+    /// Indexes `image` under `key`, replacing what was indexed before.
+    pub fn update(&mut self, key: uuid::Uuid, image: &Image) {
+        self.remove(&key);
+        let words = words_of(image);
+        for word in &words {
+            self.words.entry(word.clone()).or_default().insert(key);
+        }
+        self.indexed.insert(key, words);
+    }
+
+    pub fn remove(&mut self, key: &uuid::Uuid) {
+        for word in self.indexed.remove(key).unwrap_or_default() {
+            if let Some(keys) = self.words.get_mut(&word) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Images having, for every word of `query`, a word that starts with it.
+    pub fn search(&self, query: &str) -> Vec<uuid::Uuid> {
+        let mut found: Option<HashSet<uuid::Uuid>> = None;
+        for term in words(query) {
+            let matches: HashSet<uuid::Uuid> = self
+                .words
+                .range(term.clone()..)
+                .take_while(|(word, _)| word.starts_with(&term))
+                .flat_map(|(_, keys)| keys.iter().copied())
+                .collect();
+            found = Some(match found {
+                Some(found) => found.intersection(&matches).copied().collect(),
+                None => matches,
+            });
+        }
+        found.unwrap_or_default().into_iter().collect()
+    }
+}
+
+fn words_of(image: &Image) -> Vec<String> {
+    let mut text: Vec<&str> = image.tags.iter().map(String::as_str).collect();
+    text.push(&image.note);
+    let fields = image.metadata.fields();
+    text.extend(fields.iter().map(|(_, value)| value.as_str()));
+
+    let mut words: Vec<String> = text.into_iter().flat_map(words).collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Lowercase alphanumeric runs of `text`.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::reference::png;
+
+    fn image(tags: &[&str], note: &str) -> Image {
+        let mut image = Image::new([0., 0.], png(RgbaImage::new(2, 2))).unwrap();
+        image.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        image.note = note.to_string();
+        image
+    }
+
+    fn sorted(mut keys: Vec<uuid::Uuid>) -> Vec<uuid::Uuid> {
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn every_word_must_start_a_word_of_the_image() {
+        let mut index = Index::default();
+        let (mecha, pose) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        index.update(
+            mecha,
+            &image(&["Mecha", "lighting"], "Rim light, from the left"),
+        );
+        index.update(pose, &image(&["pose"], "mecha pose for the hero"));
+
+        assert_eq!(sorted(index.search("mech")), sorted(vec![mecha, pose]));
+        assert_eq!(index.search("MECHA light"), vec![mecha]);
+        assert_eq!(index.search("hero, mecha"), vec![pose]);
+        assert!(index.search("echa").is_empty());
+        assert!(index.search("mecha robot").is_empty());
+        assert!(index.search("").is_empty());
+    }
+
+    #[test]
+    fn updates_replace_what_was_indexed() {
+        let mut index = Index::default();
+        let key = uuid::Uuid::new_v4();
+        index.update(key, &image(&["draft"], ""));
+        index.update(key, &image(&["final"], ""));
+        assert!(index.search("draft").is_empty());
+        assert_eq!(index.search("final"), vec![key]);
+
+        index.remove(&key);
+        assert!(index.search("final").is_empty());
+        assert!(index.words.is_empty());
+    }
+}
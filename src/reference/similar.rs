@@ -0,0 +1,207 @@
+//! Near-duplicate detection with a difference hash (dHash): the image is
+//! shrunk to 9x8 grey pixels and every bit tells whether a pixel is darker
+//! than its right neighbour. Resized, recompressed or lightly edited copies
+//! of a picture end up a few bits apart.
+
+use image::DynamicImage;
+
+use super::{layout::Grid, Library};
+
+/// Differing bits, out of 64, under which two images count as similar.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let small = image.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Number of bits two perceptual hashes differ by.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Images within `threshold` of the image `key`, itself excluded.
+pub fn similar_to(library: &Library, key: &uuid::Uuid, threshold: u32) -> Vec<uuid::Uuid> {
+    let Some(hash) = library.get(key).and_then(|image| image.perceptual_hash) else {
+        return Vec::new();
+    };
+    library
+        .iter()
+        .filter(|(other, image)| {
+            *other != key
+                && image
+                    .perceptual_hash
+                    .is_some_and(|other_hash| distance(hash, other_hash) <= threshold)
+        })
+        .map(|(other, _)| *other)
+        .collect()
+}
+
+/// Sets of images that are similar to each other, directly or through
+/// another image of the set. Images without a near-duplicate are left out.
+pub fn groups(library: &Library, threshold: u32) -> Vec<Vec<uuid::Uuid>> {
+    let hashed: Vec<(uuid::Uuid, u64)> = library
+        .iter()
+        .filter_map(|(key, image)| image.perceptual_hash.map(|hash| (*key, hash)))
+        .collect();
+
+    // Union-find over the images, joined whenever a pair is close enough.
+    let mut parents: Vec<usize> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if distance(hashed[i].1, hashed[j].1) <= threshold {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<uuid::Uuid>> = Vec::new();
+    let mut group_of_root = std::collections::HashMap::new();
+    for (i, (key, _)) in hashed.iter().enumerate() {
+        let group = *group_of_root
+            .entry(root(&mut parents, i))
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        groups[group].push(*key);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Root of the set holding `i` in the union-find `parents`, flattening the
+/// path on the way.
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Moves every group to its own row under the board, so near-duplicates sit
+/// next to each other. Locked images stay where they are.
+pub fn gather(library: &mut Library, groups: &[Vec<uuid::Uuid>]) {
+    for group in groups {
+        let unlocked: Vec<_> = group
+            .iter()
+            .filter(|key| library.get(key).is_some_and(|image| !image.locked))
+            .collect();
+        if unlocked.is_empty() {
+            continue;
+        }
+        let mut grid = Grid::below(library, unlocked.len());
+        for key in unlocked {
+            let Some((position, size)) = library.rect(key) else {
+                continue;
+            };
+            let cell = grid.place(size);
+            // Images in a turned or scaled group move along its own axes.
+            let offset = [cell[0] - position[0], cell[1] - position[1]];
+            let offset = library
+                .world(key)
+                .inverse()
+                .map_or(offset, |inverse| inverse.apply_vector(offset));
+            if let Some(image) = library.get_mut(key) {
+                image.position[0] += offset[0];
+                image.position[1] += offset[1];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::reference::{group, png, Image, SPACING};
+
+    /// Gray ramp across `width` x `height`, lighter to the right or darker.
+    fn ramp(width: u32, height: u32, lighter: bool) -> Image {
+        let pixels = RgbaImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            let value = if lighter { value } else { 255 - value };
+            Rgba([value, value, value, 255])
+        });
+        Image::new([0., 0.], png(pixels)).unwrap()
+    }
+
+    #[test]
+    fn resized_copies_hash_alike() {
+        let small = ramp(64, 32, true).perceptual_hash.unwrap();
+        let large = ramp(300, 170, true).perceptual_hash.unwrap();
+        let reversed = ramp(64, 32, false).perceptual_hash.unwrap();
+        assert!(distance(small, large) <= DEFAULT_THRESHOLD);
+        assert!(distance(small, reversed) > DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn groups_join_near_duplicates_only() {
+        let mut library = Library::new();
+        let small = library.insert(ramp(64, 32, true)).unwrap();
+        let large = library.insert(ramp(300, 170, true)).unwrap();
+        let reversed = library.insert(ramp(64, 32, false)).unwrap();
+
+        let mut groups = groups(&library, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+        groups[0].sort();
+        let mut expected = vec![small, large];
+        expected.sort();
+        assert_eq!(groups[0], expected);
+        assert_eq!(similar_to(&library, &small, DEFAULT_THRESHOLD), vec![large]);
+        assert!(similar_to(&library, &reversed, DEFAULT_THRESHOLD).is_empty());
+    }
+
+    #[test]
+    fn gather_lines_up_boxes_under_the_board() {
+        let mut library = Library::new();
+        let mut first = ramp(64, 32, true);
+        first.position = [500., 40.];
+        let first = library.insert(first).unwrap();
+        let second = library.insert(ramp(300, 170, true)).unwrap();
+        // A turned group must not throw its image off the row.
+        let group_id = group::group(&mut library, None, &[second]).unwrap();
+        group::rotate(&mut library, &group_id, 90.);
+
+        let [x, y, _, height] = library.bounds().unwrap();
+        let row = y + height + SPACING;
+        gather(&mut library, &[vec![first, second]]);
+
+        let near =
+            |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3;
+        let (position, size) = library.rect(&first).unwrap();
+        assert!(near(position, [x, row]), "{:?}", position);
+        let (position, _) = library.rect(&second).unwrap();
+        assert!(
+            near(position, [x + size[0] + SPACING, row]),
+            "{:?}",
+            position
+        );
+    }
+
+    #[test]
+    fn gather_leaves_locked_images_in_place() {
+        let mut library = Library::new();
+        let mut locked = ramp(64, 32, true);
+        locked.position = [500., 40.];
+        locked.locked = true;
+        let locked = library.insert(locked).unwrap();
+        let other = library.insert(ramp(300, 170, true)).unwrap();
+
+        gather(&mut library, &[vec![locked, other]]);
+        assert_eq!(library.get(&locked).unwrap().position, [500., 40.]);
+        assert_ne!(library.get(&other).unwrap().position, [0., 0.]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Text items: section labels and notes written on the board, laid out with
+//! the bundled DejaVu Sans so every machine measures them the same way.
+
+use std::sync::OnceLock;
+
+use ab_glyph::{Font, FontRef, GlyphId, ScaleFont};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_FONT_SIZE: f32 = 32.;
+
+const FONT_DATA: &[u8] = include_bytes!("fonts/DejaVuSans.ttf");
+
+pub fn font() -> &'static FontRef<'static> {
+    static FONT: OnceLock<FontRef<'static>> = OnceLock::new();
+    FONT.get_or_init(|| FontRef::try_from_slice(FONT_DATA).expect("the bundled font is valid"))
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Text {
+    /// Top left corner of the background box.
+    pub position: [f32; 2],
+    pub content: String,
+    /// Font size in board units.
+    pub font_size: f32,
+    /// sRGB color and alpha of the glyphs.
+    pub color: [u8; 4],
+    /// Box drawn behind the text, bare text without it.
+    #[serde(default)]
+    pub background: Option<[u8; 4]>,
+}
+
+impl Text {
+    pub fn new(position: [f32; 2], content: String) -> Self {
+        Self {
+            position,
+            content,
+            font_size: DEFAULT_FONT_SIZE,
+            color: [235, 235, 235, 255],
+            background: Some([45, 45, 45, 230]),
+        }
+    }
+
+    /// Margin between the text and the edge of its box.
+    pub fn padding(&self) -> f32 {
+        self.font_size / 4.
+    }
+
+    /// Every glyph with the board position of its origin on the baseline.
+    pub fn layout(&self) -> Vec<(GlyphId, [f32; 2])> {
+        self.shape().0
+    }
+
+    /// Size of the box, in board units.
+    pub fn size(&self) -> [f32; 2] {
+        self.shape().1
+    }
+
+    fn shape(&self) -> (Vec<(GlyphId, [f32; 2])>, [f32; 2]) {
+        let font = font().as_scaled(self.font_size);
+        let padding = self.padding();
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let mut width: f32 = 0.;
+        let mut lines = 0;
+        for (index, line) in self.content.split('\n').enumerate() {
+            let baseline = self.position[1] + padding + font.ascent() + index as f32 * line_height;
+            let mut x = self.position[0] + padding;
+            let mut previous = None;
+            for character in line.chars().filter(|c| !c.is_control()) {
+                let glyph = font.glyph_id(character);
+                if let Some(previous) = previous {
+                    x += font.kern(previous, glyph);
+                }
+                glyphs.push((glyph, [x, baseline]));
+                x += font.h_advance(glyph);
+                previous = Some(glyph);
+            }
+            width = width.max(x - self.position[0] - padding);
+            lines += 1;
+        }
+
+        let height = lines as f32 * line_height - font.line_gap();
+        (glyphs, [width + 2. * padding, height + 2. * padding])
+    }
+}
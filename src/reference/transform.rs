@@ -0,0 +1,187 @@
+//! 2D affine transforms placing the contents of a group on the board.
+
+use serde::{Deserialize, Serialize};
+
+/// Affine transform `[a, b, c, d, tx, ty]`, mapping `(x, y)` to
+/// `(a x + c y + tx, b x + d y + ty)`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform(pub [f32; 6]);
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self([1., 0., 0., 1., 0., 0.]);
+
+    pub fn translation(offset: [f32; 2]) -> Self {
+        Self([1., 0., 0., 1., offset[0], offset[1]])
+    }
+
+    /// Rotation by `angle` radians, clockwise on screen, around `pivot`.
+    pub fn rotation_about(angle: f32, pivot: [f32; 2]) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::translation(pivot)
+            .then(&Self([cos, sin, -sin, cos, 0., 0.]))
+            .then(&Self::translation([-pivot[0], -pivot[1]]))
+    }
+
+    /// Uniform scaling by `factor` around `pivot`.
+    pub fn scaling_about(factor: f32, pivot: [f32; 2]) -> Self {
+        Self::translation(pivot)
+            .then(&Self([factor, 0., 0., factor, 0., 0.]))
+            .then(&Self::translation([-pivot[0], -pivot[1]]))
+    }
+
+    /// Transform applying `inner` first, then `self`.
+    pub fn then(&self, inner: &Transform) -> Transform {
+        let [a, b, c, d, tx, ty] = self.0;
+        let [e, f, g, h, ux, uy] = inner.0;
+        Transform([
+            a * e + c * f,
+            b * e + d * f,
+            a * g + c * h,
+            b * g + d * h,
+            a * ux + c * uy + tx,
+            b * ux + d * uy + ty,
+        ])
+    }
+
+    pub fn apply(&self, point: [f32; 2]) -> [f32; 2] {
+        let [a, b, c, d, tx, ty] = self.0;
+        [
+            a * point[0] + c * point[1] + tx,
+            b * point[0] + d * point[1] + ty,
+        ]
+    }
+
+    /// Applies the transform to a direction, ignoring the translation.
+    pub fn apply_vector(&self, vector: [f32; 2]) -> [f32; 2] {
+        let [a, b, c, d, _, _] = self.0;
+        [a * vector[0] + c * vector[1], b * vector[0] + d * vector[1]]
+    }
+
+    /// None when the transform squashes the plane, e.g. a zero scale.
+    pub fn inverse(&self) -> Option<Transform> {
+        let [a, b, c, d, tx, ty] = self.0;
+        let determinant = a * d - b * c;
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let [ia, ib, ic, id] = [
+            d / determinant,
+            -b / determinant,
+            -c / determinant,
+            a / determinant,
+        ];
+        Some(Transform([
+            ia,
+            ib,
+            ic,
+            id,
+            -(ia * tx + ic * ty),
+            -(ib * tx + id * ty),
+        ]))
+    }
+
+    /// Whether the transform only moves things, without turning or scaling.
+    pub fn is_translation(&self) -> bool {
+        let [a, b, c, d, _, _] = self.0;
+        [a - 1., b, c, d - 1.]
+            .iter()
+            .all(|value| value.abs() < 1e-6)
+    }
+
+    pub fn offset(&self) -> [f32; 2] {
+        [self.0[4], self.0[5]]
+    }
+
+    pub fn scale(&self) -> f32 {
+        let [a, b, c, d, _, _] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+
+    /// Box `(position, size)` around the rectangle at `position` of `size`
+    /// once transformed.
+    pub fn bounds(&self, position: [f32; 2], size: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let [x, y] = position;
+        let corners = [
+            self.apply([x, y]),
+            self.apply([x + size[0], y]),
+            self.apply([x, y + size[1]]),
+            self.apply([x + size[0], y + size[1]]),
+        ];
+        let [left, top, right, bottom] = corners.iter().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[left, top, right, bottom], [x, y]| {
+                [left.min(*x), top.min(*y), right.max(*x), bottom.max(*y)]
+            },
+        );
+        ([left, top], [right - left, bottom - top])
+    }
+
+    /// Whether the rectangle at `position` of `size`, once transformed,
+    /// covers the board point `point`.
+    pub fn covers(&self, position: [f32; 2], size: [f32; 2], point: [f32; 2]) -> bool {
+        let Some(inverse) = self.inverse() else {
+            return false;
+        };
+        let [x, y] = inverse.apply(point);
+        x >= position[0]
+            && x <= position[0] + size[0]
+            && y >= position[1]
+            && y <= position[1] + size[1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn then_applies_the_inner_transform_first() {
+        let outer = Transform::translation([10., 0.]);
+        let inner = Transform::scaling_about(2., [0., 0.]);
+        assert_near(outer.then(&inner).apply([1., 1.]), [12., 2.]);
+        assert_near(inner.then(&outer).apply([1., 1.]), [22., 2.]);
+    }
+
+    #[test]
+    fn rotation_turns_clockwise_around_the_pivot() {
+        let rotation = Transform::rotation_about(90_f32.to_radians(), [5., 5.]);
+        assert_near(rotation.apply([5., 5.]), [5., 5.]);
+        // Right of the pivot goes below it, y pointing down.
+        assert_near(rotation.apply([6., 5.]), [5., 6.]);
+        assert_near(rotation.apply_vector([1., 0.]), [0., 1.]);
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform::translation([30., -12.])
+            .then(&Transform::rotation_about(0.7, [4., 9.]))
+            .then(&Transform::scaling_about(1.5, [-2., 3.]));
+        let inverse = transform.inverse().unwrap();
+        for point in [[0., 0.], [17., -4.], [-250., 80.]] {
+            assert_near(inverse.apply(transform.apply(point)), point);
+        }
+        assert!(transform.then(&inverse).is_translation());
+        assert_near(transform.then(&inverse).offset(), [0., 0.]);
+    }
+
+    #[test]
+    fn flat_transforms_have_no_inverse() {
+        assert!(Transform::scaling_about(0., [1., 1.]).inverse().is_none());
+        assert_eq!(Transform::IDENTITY.inverse(), Some(Transform::IDENTITY));
+    }
+}
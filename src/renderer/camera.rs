@@ -0,0 +1,52 @@
+/// View on the board. Board coordinates are pixels with the y axis pointing
+/// down, `zoom` is the number of screen pixels per board pixel.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub center: [f32; 2],
+    pub zoom: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct CameraUniform {
+    center: [f32; 2],
+    scale: [f32; 2],
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            center: [0., 0.],
+            zoom: 1.,
+        }
+    }
+}
+
+impl Camera {
+    /// Camera showing the whole `[x, y, width, height]` region in a target of
+    /// the given size, keeping the aspect ratio.
+    pub fn fit(region: [f32; 4], width: u32, height: u32) -> Self {
+        let zoom = (width as f32 / region[2]).min(height as f32 / region[3]);
+        Self {
+            center: [region[0] + region[2] / 2., region[1] + region[3] / 2.],
+            zoom,
+        }
+    }
+
+    pub fn screen_to_world(&self, position: [f32; 2], width: u32, height: u32) -> [f32; 2] {
+        [
+            self.center[0] + (position[0] - width as f32 / 2.) / self.zoom,
+            self.center[1] + (position[1] - height as f32 / 2.) / self.zoom,
+        ]
+    }
+
+    pub(super) fn uniform(&self, width: u32, height: u32) -> CameraUniform {
+        CameraUniform {
+            center: self.center,
+            scale: [
+                2. * self.zoom / width as f32,
+                -2. * self.zoom / height as f32,
+            ],
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Color chip drawing. The color and the strip behind the label are flat
+//! panels, the label goes with the texts and shares their glyph atlas.
+
+use std::collections::HashSet;
+
+use super::{
+    text::{AtlasFull, TextComponent},
+    vector::{self, Panel, VectorComponent},
+    Renderer,
+};
+use crate::reference::chip;
+
+const LABEL_STRIP: [u8; 4] = [235, 235, 235, 255];
+const OUTLINE: [u8; 4] = [110, 110, 110, 255];
+/// Outline width in board units.
+const OUTLINE_WIDTH: f32 = 1.;
+
+pub(super) struct ChipComponent {
+    panels: Option<VectorComponent>,
+    label: Option<TextComponent>,
+}
+
+impl Renderer {
+    /// Builds the panels and label of a chip after it was added, edited or
+    /// moved, or drops them if it is gone.
+    pub fn draw_chip(&mut self, chip_id: uuid::Uuid) {
+        if self.try_draw_chip(chip_id).is_err() {
+            self.redraw_glyphs();
+        }
+    }
+
+    pub(super) fn try_draw_chip(&mut self, chip_id: uuid::Uuid) -> Result<(), AtlasFull> {
+        let Some(chip) = self.library.get_chip(&chip_id) else {
+            self.chip_components.remove(&chip_id);
+            return Ok(());
+        };
+
+        let [x, y] = chip.position;
+        let [width, height] = chip.size();
+        let panels = [
+            Panel {
+                position: [x, y + chip::SIZE],
+                size: [width, height - chip::SIZE],
+                fill: Some(LABEL_STRIP),
+                stroke: None,
+            },
+            Panel {
+                position: chip.position,
+                size: [width, chip::SIZE],
+                fill: Some(chip.color),
+                stroke: None,
+            },
+            Panel {
+                position: chip.position,
+                size: [width, height],
+                fill: None,
+                stroke: Some((OUTLINE, OUTLINE_WIDTH)),
+            },
+        ];
+        let world = self.library.world(&chip_id);
+        let label = TextComponent::new(
+            &self.device,
+            &self.queue,
+            &mut self.atlas,
+            &chip.label(),
+            &world,
+        )?;
+        let component = ChipComponent {
+            panels: vector::panels(&self.device, &panels, world),
+            label,
+        };
+        self.chip_components.insert(chip_id, component);
+        Ok(())
+    }
+
+    pub(super) fn encode_chips<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        hidden: &HashSet<uuid::Uuid>,
+    ) {
+        render_pass.set_pipeline(&self.vector_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for (chip_id, component) in &self.chip_components {
+            if hidden.contains(chip_id) {
+                continue;
+            }
+            if let Some(panels) = &component.panels {
+                panels.encode(render_pass);
+            }
+        }
+    }
+
+    /// Labels, with the text pipeline already set.
+    pub(super) fn encode_chip_labels<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        hidden: &HashSet<uuid::Uuid>,
+    ) {
+        for (chip_id, component) in &self.chip_components {
+            if hidden.contains(chip_id) {
+                continue;
+            }
+            if let Some(label) = &component.label {
+                label.encode(render_pass);
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Crop mode overlay: the outline of all the pixels of the image, the shown
+//! rectangle and its handles, drawn over the board.
+
+use super::{
+    vector::{self, Panel},
+    Renderer,
+};
+use crate::reference::crop;
+
+const OUTLINE: [u8; 4] = [150, 150, 150, 255];
+const CROP: [u8; 4] = [255, 200, 40, 255];
+const HANDLE: [u8; 4] = [255, 255, 255, 255];
+/// Handle side and outline width in screen pixels.
+pub const HANDLE_SIZE: f32 = 10.;
+const LINE_WIDTH: f32 = 1.5;
+
+impl Renderer {
+    /// Shows the crop handles of `image_id`, or none.
+    pub fn set_crop_image(&mut self, image_id: Option<uuid::Uuid>) {
+        self.crop_image = image_id;
+        self.draw_crop_handles();
+    }
+
+    /// Builds the overlay again, for the current camera zoom.
+    pub(super) fn draw_crop_handles(&mut self) {
+        self.crop_handles = self.crop_image.and_then(|image_id| {
+            let image = self.library.get(&image_id)?;
+            let world = self.library.world(&image_id);
+            // The overlay keeps its size on screen whatever the zoom.
+            let unit = 1. / (self.camera.zoom * world.scale()).max(f32::EPSILON);
+            let line = (OUTLINE, LINE_WIDTH * unit);
+
+            let [x, y] = image.position;
+            let [crop_x, crop_y, _, _] = image.crop_rect();
+            let [full_width, full_height] = image.full_size();
+            let size = image.size();
+            let mut panels = vec![
+                Panel {
+                    position: [x - crop_x as f32, y - crop_y as f32],
+                    size: [full_width as f32, full_height as f32],
+                    fill: None,
+                    stroke: Some(line),
+                },
+                Panel {
+                    position: image.position,
+                    size,
+                    fill: None,
+                    stroke: Some((CROP, LINE_WIDTH * unit)),
+                },
+            ];
+            let side = HANDLE_SIZE * unit;
+            for edges in crop::HANDLES {
+                let [hx, hy] = crop::handle_position(edges, size);
+                panels.push(Panel {
+                    position: [x + hx - side / 2., y + hy - side / 2.],
+                    size: [side, side],
+                    fill: Some(HANDLE),
+                    stroke: Some((CROP, LINE_WIDTH * unit)),
+                });
+            }
+            vector::panels(&self.device, &panels, world)
+        });
+    }
+
+    /// The overlay, over everything else.
+    pub(super) fn encode_crop_handles<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(handles) = &self.crop_handles {
+            render_pass.set_pipeline(&self.vector_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            handles.encode(render_pass);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Frame drawing. The body and title bar are panels drawn under the images,
+//! the title goes with the texts and shares their glyph atlas.
+
+use super::{
+    text::{AtlasFull, TextComponent},
+    vector::{self, Panel, VectorComponent},
+    Renderer,
+};
+use crate::reference::transform::Transform;
+
+const BODY: [u8; 4] = [58, 58, 58, 255];
+const OUTLINE: [u8; 4] = [110, 110, 110, 255];
+const TITLE_BAR: [u8; 4] = [40, 40, 40, 255];
+/// Outline width in board units.
+const OUTLINE_WIDTH: f32 = 2.;
+
+pub(super) struct FrameComponent {
+    panels: Option<VectorComponent>,
+    title: Option<TextComponent>,
+}
+
+impl Renderer {
+    /// Builds the panels and title of a frame after it was added, edited,
+    /// moved or collapsed, or drops them if it is gone.
+    pub fn draw_frame(&mut self, frame_id: uuid::Uuid) {
+        if self.try_draw_frame(frame_id).is_err() {
+            self.redraw_glyphs();
+        }
+    }
+
+    pub(super) fn try_draw_frame(&mut self, frame_id: uuid::Uuid) -> Result<(), AtlasFull> {
+        let Some(frame) = self.library.get_frame(&frame_id) else {
+            self.frame_components.remove(&frame_id);
+            return Ok(());
+        };
+
+        let (title_position, title_size) = frame.title_rect();
+        let mut panels = vec![Panel {
+            position: title_position,
+            size: title_size,
+            fill: Some(TITLE_BAR),
+            stroke: None,
+        }];
+        if !frame.collapsed {
+            panels.push(Panel {
+                position: frame.position,
+                size: frame.size,
+                fill: Some(BODY),
+                stroke: Some((OUTLINE, OUTLINE_WIDTH)),
+            });
+        }
+        let title = TextComponent::new(
+            &self.device,
+            &self.queue,
+            &mut self.atlas,
+            &frame.title(),
+            &Transform::IDENTITY,
+        )?;
+        let component = FrameComponent {
+            panels: vector::panels(&self.device, &panels, Transform::IDENTITY),
+            title,
+        };
+        self.frame_components.insert(frame_id, component);
+        Ok(())
+    }
+
+    pub(super) fn encode_frames<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.vector_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for component in self.frame_components.values() {
+            if let Some(panels) = &component.panels {
+                panels.encode(render_pass);
+            }
+        }
+    }
+
+    /// Titles, with the text pipeline already set.
+    pub(super) fn encode_frame_titles<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        for component in self.frame_components.values() {
+            if let Some(title) = &component.title {
+                title.encode(render_pass);
+            }
+        }
+    }
+}
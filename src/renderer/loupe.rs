@@ -0,0 +1,89 @@
+//! Eyedropper loupe: the pixels around the cursor, magnified next to it.
+
+use super::{
+    vector::{self, Panel},
+    Renderer,
+};
+use crate::reference::transform::Transform;
+
+/// Side of a magnified pixel, and gap between the cursor and the loupe, in
+/// screen pixels.
+const CELL: f32 = 12.;
+const GAP: f32 = 24.;
+const BACKGROUND: [u8; 4] = [30, 30, 30, 255];
+const PICKED: [u8; 4] = [255, 255, 255, 255];
+
+/// What the loupe shows.
+#[derive(PartialEq)]
+pub struct Loupe {
+    /// Board point of the cursor.
+    pub position: [f32; 2],
+    /// Square of pixels centered on the picked one, row by row, None out of
+    /// the image.
+    pub pixels: Vec<Option<[u8; 4]>>,
+}
+
+impl Renderer {
+    /// Shows `loupe`, or none. Returns whether that changed anything.
+    pub fn set_loupe(&mut self, loupe: Option<Loupe>) -> bool {
+        if self.loupe == loupe {
+            return false;
+        }
+        self.loupe = loupe;
+        self.draw_loupe();
+        true
+    }
+
+    /// Builds the loupe again, for the current camera zoom.
+    pub(super) fn draw_loupe(&mut self) {
+        self.loupe_panels = self.loupe.as_ref().and_then(|loupe| {
+            let side = (loupe.pixels.len() as f32).sqrt() as usize;
+            if side == 0 {
+                return None;
+            }
+            // The loupe keeps its size on screen whatever the zoom.
+            let unit = 1. / self.camera.zoom.max(f32::EPSILON);
+            let cell = CELL * unit;
+            let origin = [
+                loupe.position[0] + GAP * unit,
+                loupe.position[1] + GAP * unit,
+            ];
+            let mut panels = vec![Panel {
+                position: origin,
+                size: [cell * side as f32, cell * side as f32],
+                fill: Some(BACKGROUND),
+                stroke: Some((BACKGROUND, 2. * unit)),
+            }];
+            for (index, color) in loupe.pixels.iter().enumerate() {
+                let Some(color) = color else {
+                    continue;
+                };
+                panels.push(Panel {
+                    position: [
+                        origin[0] + (index % side) as f32 * cell,
+                        origin[1] + (index / side) as f32 * cell,
+                    ],
+                    size: [cell, cell],
+                    fill: Some(*color),
+                    stroke: None,
+                });
+            }
+            let center = (side / 2) as f32 * cell;
+            panels.push(Panel {
+                position: [origin[0] + center, origin[1] + center],
+                size: [cell, cell],
+                fill: None,
+                stroke: Some((PICKED, 2. * unit)),
+            });
+            vector::panels(&self.device, &panels, Transform::IDENTITY)
+        });
+    }
+
+    pub(super) fn encode_loupe<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(panels) = &self.loupe_panels {
+            render_pass.set_pipeline(&self.vector_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            panels.encode(render_pass);
+        }
+    }
+}
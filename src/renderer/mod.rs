@@ -1,9 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use wgpu::util::DeviceExt;
 use winit::{event::WindowEvent, window::Window};
 
-use crate::reference::{Image, Library};
+use crate::reference::{
+    adjust::Adjustments, annotation::Annotation, group, text::Text, Image, Library, SPACING,
+};
+
+pub use camera::Camera;
+pub use loupe::Loupe;
+pub use value::ValueMode;
+pub use vision::Vision;
+
+mod camera;
+mod chip;
+mod crop;
+mod frame;
+mod loupe;
+mod offscreen;
+mod palette;
+mod post;
+mod text;
+mod value;
+mod vector;
+mod vision;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -33,28 +53,700 @@ impl Vertex {
     }
 }
 
+/// `Adjustments` as `fs_main` reads them.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AdjustmentsUniform {
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+    /// In radians.
+    hue: f32,
+    gamma: f32,
+    invert: f32,
+    grayscale: f32,
+    _padding: f32,
+}
+
+impl From<Adjustments> for AdjustmentsUniform {
+    fn from(adjustments: Adjustments) -> Self {
+        let flag = |set: bool| if set { 1. } else { 0. };
+        Self {
+            brightness: adjustments.brightness,
+            contrast: adjustments.contrast,
+            saturation: adjustments.saturation,
+            hue: adjustments.hue.to_radians(),
+            gamma: adjustments.gamma,
+            invert: flag(adjustments.invert),
+            grayscale: flag(adjustments.grayscale),
+            _padding: 0.,
+        }
+    }
+}
+
 struct GraphicComponent {
-    render_pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
-    diffuse_bind_group: wgpu::BindGroup,
+    /// Content key of the image, its bind group lives in `Renderer::textures`.
+    texture: String,
     vertices: Vec<Vertex>,
+    /// Color adjustments of this image alone, at group 2.
+    adjustments: wgpu::BindGroup,
 }
 
-pub struct State {
-    window: Window,
-    surface: wgpu::Surface,
+/// Draws the board into any texture of `format`. It owns no window so it can
+/// run headless, `State` adds the window and its surface on top of it.
+pub struct Renderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    pub size: winit::dpi::PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+
+    render_pipeline: wgpu::RenderPipeline,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    adjustments_bind_group_layout: wgpu::BindGroupLayout,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
 
     context: HashMap<uuid::Uuid, GraphicComponent>,
+    text_pipeline: wgpu::RenderPipeline,
+    atlas: text::GlyphAtlas,
+    text_components: HashMap<uuid::Uuid, text::TextComponent>,
+    vector_pipeline: wgpu::RenderPipeline,
+    vector_components: HashMap<uuid::Uuid, vector::VectorComponent>,
+    frame_components: HashMap<uuid::Uuid, frame::FrameComponent>,
+    chip_components: HashMap<uuid::Uuid, chip::ChipComponent>,
+    /// Swatches of the extracted palettes, by image.
+    palette_components: HashMap<uuid::Uuid, vector::VectorComponent>,
+    /// Image in crop mode and its handles.
+    crop_image: Option<uuid::Uuid>,
+    crop_handles: Option<vector::VectorComponent>,
+    /// Eyedropper loupe and its panels.
+    loupe: Option<Loupe>,
+    loupe_panels: Option<vector::VectorComponent>,
+    post: post::PostProcess,
+    /// Color vision simulated on screen and in renders.
+    vision: Vision,
+    /// Images matching the search being typed, the others are left out.
+    found: Option<HashSet<uuid::Uuid>>,
+    /// One texture per distinct content, shared by the images that show it.
+    textures: HashMap<String, wgpu::BindGroup>,
 
     clear_color: wgpu::Color,
+    pub camera: Camera,
 
     library: Library,
 }
 
+impl Renderer {
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("texture bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("camera bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let adjustments_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("adjustments bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let camera = Camera::default();
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera.uniform(1, 1)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera_bind_group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // Texts share the layout above, only images have adjustments.
+        let image_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Image Pipeline Layout"),
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &adjustments_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&image_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let text_pipeline = text::pipeline(&device, &render_pipeline_layout, format);
+        let atlas = text::GlyphAtlas::new(&device, &queue, &texture_bind_group_layout);
+        let vector_pipeline = vector::pipeline(&device, &camera_bind_group_layout, format);
+        let post = post::PostProcess::new(&device, format);
+
+        let clear_color = wgpu::Color {
+            r: 0.1,
+            g: 0.1,
+            b: 0.1,
+            a: 1.0,
+        };
+
+        let context = HashMap::new();
+        let library = Library::new();
+
+        Self {
+            device,
+            queue,
+            format,
+
+            render_pipeline,
+            texture_bind_group_layout,
+            adjustments_bind_group_layout,
+            camera_buffer,
+            camera_bind_group,
+
+            context,
+            text_pipeline,
+            atlas,
+            text_components: HashMap::new(),
+            vector_pipeline,
+            vector_components: HashMap::new(),
+            frame_components: HashMap::new(),
+            chip_components: HashMap::new(),
+            palette_components: HashMap::new(),
+            crop_image: None,
+            crop_handles: None,
+            loupe: None,
+            loupe_panels: None,
+            post,
+            vision: Vision::Normal,
+            found: None,
+            textures: HashMap::new(),
+
+            clear_color,
+            camera,
+
+            library,
+        }
+    }
+
+    /// Renderer without any window, for exports and thumbnails.
+    pub async fn headless() -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        Some(Self::new(
+            device,
+            queue,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ))
+    }
+
+    pub fn library(&self) -> &Library {
+        &self.library
+    }
+
+    /// Direct access to the board, changed images must be `draw`n again.
+    pub fn library_mut(&mut self) -> &mut Library {
+        &mut self.library
+    }
+
+    fn get_image_from_library(&self, image_id: uuid::Uuid) -> Option<&Image> {
+        self.library.get(&image_id)
+    }
+
+    pub fn add_image_to_library(&mut self, image: Image) -> Option<uuid::Uuid> {
+        self.library.insert(image)
+    }
+
+    /// Replaces the whole board and uploads every image to the GPU.
+    pub fn set_library(&mut self, library: Library) {
+        self.library = library;
+        self.context.clear();
+        self.textures.clear();
+        self.text_components.clear();
+        self.vector_components.clear();
+        self.frame_components.clear();
+        self.chip_components.clear();
+        self.palette_components.clear();
+        self.crop_image = None;
+        self.crop_handles = None;
+        let keys: Vec<uuid::Uuid> = self.library.keys().copied().collect();
+        for key in keys {
+            self.draw(key);
+        }
+        let text_ids: Vec<uuid::Uuid> = self.library.texts().map(|(id, _)| *id).collect();
+        for text_id in text_ids {
+            self.draw_text(text_id);
+        }
+        self.draw_chips();
+        self.draw_annotations();
+    }
+
+    /// Adds every image of `library` to the board and uploads them.
+    pub fn extend_library(&mut self, library: Library) {
+        let keys: Vec<uuid::Uuid> = library.keys().copied().collect();
+        let text_ids: Vec<uuid::Uuid> = library.texts().map(|(id, _)| *id).collect();
+        self.library.extend(library);
+        for key in keys {
+            self.draw(key);
+        }
+        for text_id in text_ids {
+            self.draw_text(text_id);
+        }
+        self.draw_chips();
+        self.draw_annotations();
+    }
+
+    fn draw_chips(&mut self) {
+        let chip_ids: Vec<uuid::Uuid> = self.library.chips().map(|(id, _)| *id).collect();
+        for chip_id in chip_ids {
+            self.draw_chip(chip_id);
+        }
+    }
+
+    /// Builds the annotations and frames of the whole board.
+    fn draw_annotations(&mut self) {
+        let annotation_ids: Vec<uuid::Uuid> =
+            self.library.annotations().map(|(id, _)| *id).collect();
+        for annotation_id in annotation_ids {
+            self.draw_annotation(annotation_id);
+        }
+        let frame_ids: Vec<uuid::Uuid> = self.library.frames().map(|(id, _)| *id).collect();
+        for frame_id in frame_ids {
+            self.draw_frame(frame_id);
+        }
+    }
+
+    /// Draws again the image, text, chip, annotation, group or frame `key`,
+    /// whichever it is.
+    pub fn redraw(&mut self, key: uuid::Uuid) {
+        if self.library.get_group(&key).is_some() {
+            for member in group::members(&self.library, &key) {
+                self.redraw(member);
+            }
+        } else if self.library.get(&key).is_some() {
+            self.draw(key);
+        } else if self.library.get_text(&key).is_some() {
+            self.draw_text(key);
+        } else if self.library.get_chip(&key).is_some() {
+            self.draw_chip(key);
+        } else if self.library.get_annotation(&key).is_some() {
+            self.draw_annotation(key);
+        } else {
+            // Also drops the frame when it is gone.
+            self.draw_frame(key);
+        }
+    }
+
+    /// Swaps the pixels of an image for the ones of `reloaded`, a new decode
+    /// of its file, keeping where it sits on the board.
+    pub fn replace_image(&mut self, image_id: uuid::Uuid, reloaded: &Image) {
+        self.library.reload(&image_id, reloaded);
+        self.draw(image_id);
+    }
+
+    #[allow(clippy::single_match)]
+    pub fn draw(&mut self, image_id: uuid::Uuid) {
+        let image = self.get_image_from_library(image_id);
+
+        match image {
+            Some(image) => {
+                let texture = image.content_key().to_string();
+                let diffuse_bind_group = if self.textures.contains_key(&texture) {
+                    None
+                } else {
+                    Some(self.upload(&image.image))
+                };
+
+                let [x, y] = image.position;
+                let [width, height] = image.size();
+                // Groups may turn and scale the image, its corners are placed
+                // on the board by the composed transform rather than moved.
+                let world = self.library.world(&image_id);
+                let corner = |point: [f32; 2], texture_coordinates: [f32; 2]| {
+                    let [px, py] = world.apply(point);
+                    Vertex {
+                        position: [px, py, 0.],
+                        texture_coordinates,
+                    }
+                };
+
+                // Only the cropped part of the texture is sampled.
+                let [u0, v0, u1, v1] = image.uv();
+
+                let vertices = vec![
+                    corner([x, y], [u0, v0]),                  // A
+                    corner([x + width, y], [u1, v0]),          // B
+                    corner([x, y + height], [u0, v1]),         // D
+                    corner([x + width, y], [u1, v0]),          // B
+                    corner([x + width, y + height], [u1, v1]), // C
+                    corner([x, y + height], [u0, v1]),         // D
+                ];
+
+                let vertex_buffer =
+                    self.device
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        });
+
+                let uniform = AdjustmentsUniform::from(image.adjustments);
+                let adjustments_buffer =
+                    self.device
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Adjustments Buffer"),
+                            contents: bytemuck::cast_slice(&[uniform]),
+                            usage: wgpu::BufferUsages::UNIFORM,
+                        });
+                let adjustments = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("adjustments_bind_group"),
+                    layout: &self.adjustments_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: adjustments_buffer.as_entire_binding(),
+                    }],
+                });
+
+                if let Some(diffuse_bind_group) = diffuse_bind_group {
+                    self.textures.insert(texture.clone(), diffuse_bind_group);
+                }
+                let component = GraphicComponent {
+                    vertex_buffer,
+                    texture,
+                    vertices,
+                    adjustments,
+                };
+
+                let previous = self.context.insert(image_id, component);
+
+                // Reloads and relinks may leave the previous content unused.
+                let changed = previous.filter(|previous| {
+                    self.context
+                        .get(&image_id)
+                        .is_some_and(|component| component.texture != previous.texture)
+                });
+                if let Some(previous) = changed {
+                    if !self.context.values().any(|c| c.texture == previous.texture) {
+                        self.textures.remove(&previous.texture);
+                    }
+                }
+
+                // The image may have moved, its annotations go with it.
+                for annotation_id in self.library.annotations_on(&image_id) {
+                    self.draw_annotation(annotation_id);
+                }
+                self.draw_palette(image_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Uploads `diffuse_image` to a new texture and returns the bind group
+    /// sampling it.
+    fn upload(&self, diffuse_image: &image::DynamicImage) -> wgpu::BindGroup {
+        let diffuse_rgba = diffuse_image.to_rgba8();
+
+        use image::GenericImageView;
+        let dimensions = diffuse_image.dimensions();
+
+        let texture_size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+
+        let diffuse_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("diffuse_texture"),
+            view_formats: &[],
+        });
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &diffuse_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &diffuse_rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            texture_size,
+        );
+
+        let diffuse_texture_view =
+            diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let diffuse_sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let diffuse_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("diffuse_bind_group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
+                },
+            ],
+        });
+
+        diffuse_bind_group
+    }
+
+    /// Records the board draw into `view`, a target of `width` x `height`
+    /// pixels, as seen through `camera`. The crop handles and the loupe are
+    /// drawn over it only with `overlays`.
+    fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        overlays: bool,
+    ) {
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[camera.uniform(width, height)]),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        let hidden = match &self.found {
+            Some(found) => self.library.hidden_except(|key| found.contains(key)),
+            None => self.library.hidden(),
+        };
+        self.encode_frames(&mut render_pass);
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        for (image_id, component) in &self.context {
+            if hidden.contains(image_id) {
+                continue;
+            }
+            render_pass.set_bind_group(0, &self.textures[&component.texture], &[]);
+            render_pass.set_bind_group(2, &component.adjustments, &[]);
+            render_pass.set_vertex_buffer(0, component.vertex_buffer.slice(..));
+            render_pass.draw(0..component.vertices.len() as u32, 0..1);
+        }
+        self.encode_palettes(&mut render_pass, &hidden);
+        self.encode_chips(&mut render_pass, &hidden);
+        self.encode_annotations(&mut render_pass, &hidden);
+        self.encode_texts(&mut render_pass, &hidden);
+        if overlays {
+            self.encode_crop_handles(&mut render_pass);
+            self.encode_loupe(&mut render_pass);
+        }
+    }
+
+    /// Records the board into `view` as `encode` does, through the
+    /// post-process pass when a vision is simulated or `value_mode` is on.
+    /// The board is drawn into `target` first, made again when missing or of
+    /// another size.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_post(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        value_mode: ValueMode,
+        overlays: bool,
+        target: &mut Option<post::Target>,
+    ) {
+        if self.vision == Vision::Normal && value_mode == ValueMode::Off {
+            self.encode(encoder, view, camera, width, height, overlays);
+            return;
+        }
+        let size = [width, height];
+        if target.as_ref().is_none_or(|target| target.size() != size) {
+            *target = Some(self.post.target(&self.device, size));
+        }
+        let Some(target) = target.as_ref() else {
+            return;
+        };
+        self.encode(encoder, &target.view, camera, width, height, overlays);
+        self.post
+            .encode(encoder, &self.queue, target, view, self.vision, value_mode);
+    }
+
+    pub fn vision(&self) -> Vision {
+        self.vision
+    }
+
+    pub fn set_vision(&mut self, vision: Vision) {
+        self.vision = vision;
+    }
+
+    /// Draws only the images `found`, or all of them. Returns whether that
+    /// changed anything.
+    pub fn set_found(&mut self, found: Option<HashSet<uuid::Uuid>>) -> bool {
+        if self.found == found {
+            return false;
+        }
+        self.found = found;
+        true
+    }
+}
+
+pub struct State {
+    window: Window,
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+    pub size: winit::dpi::PhysicalSize<u32>,
+
+    renderer: Renderer,
+    /// Board wide view of the values only, drawn by the post-process pass.
+    value_mode: ValueMode,
+    /// The board before the post-process pass, sized like the window.
+    post_target: Option<post::Target>,
+
+    dirty: bool,
+}
+
 impl State {
     pub async fn new(window: Window) -> Self {
         let size = window.inner_size();
@@ -108,246 +800,151 @@ impl State {
 
         surface.configure(&device, &config);
 
-        let clear_color = wgpu::Color {
-            r: 0.1,
-            g: 0.1,
-            b: 0.1,
-            a: 1.0,
-        };
-
-        let context = HashMap::new();
-        let library = Library::new();
+        let renderer = Renderer::new(device, queue, surface_format);
 
         Self {
             window,
             surface,
-            device,
-            queue,
             config,
             size,
 
-            context,
+            renderer,
+            value_mode: ValueMode::Off,
+            post_target: None,
 
-            clear_color,
-            library,
+            dirty: true,
         }
     }
 
-    fn get_image_from_library(&self, image_id: uuid::Uuid) -> Option<&Image> {
-        self.library.get(&image_id)
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    pub fn library_mut(&mut self) -> &mut Library {
+        self.renderer.library_mut()
     }
 
     pub fn add_image_to_library(&mut self, image: Image) -> Option<uuid::Uuid> {
-        self.library.insert(image)
+        self.renderer.add_image_to_library(image)
+    }
+
+    pub fn replace_image(&mut self, image_id: uuid::Uuid, reloaded: &Image) {
+        self.renderer.replace_image(image_id, reloaded);
+        self.dirty = true;
+    }
+
+    pub fn set_library(&mut self, library: Library) {
+        self.renderer.set_library(library);
+        self.dirty = true;
+    }
+
+    pub fn extend_library(&mut self, library: Library) {
+        self.renderer.extend_library(library);
+        self.dirty = true;
     }
 
     pub fn draw(&mut self, image_id: uuid::Uuid) {
-        let image = self.get_image_from_library(image_id);
+        self.renderer.draw(image_id);
+        self.dirty = true;
+    }
 
-        match image {
-            Some(image) => {
-                let diffuse_image = &image.image;
-                let diffuse_rgba = diffuse_image.to_rgba8();
+    pub fn add_text_to_library(&mut self, text: Text) -> uuid::Uuid {
+        self.dirty = true;
+        self.renderer.add_text_to_library(text)
+    }
 
-                use image::GenericImageView;
-                let dimensions = diffuse_image.dimensions();
+    pub fn draw_text(&mut self, text_id: uuid::Uuid) {
+        self.renderer.draw_text(text_id);
+        self.dirty = true;
+    }
 
-                let texture_size = wgpu::Extent3d {
-                    width: dimensions.0,
-                    height: dimensions.1,
-                    depth_or_array_layers: 1,
-                };
+    pub fn add_annotation_to_library(&mut self, annotation: Annotation) -> uuid::Uuid {
+        self.dirty = true;
+        self.renderer.add_annotation_to_library(annotation)
+    }
 
-                let diffuse_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                    size: texture_size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    label: Some("diffuse_texture"),
-                    view_formats: &[],
-                });
+    pub fn draw_annotation(&mut self, annotation_id: uuid::Uuid) {
+        self.renderer.draw_annotation(annotation_id);
+        self.dirty = true;
+    }
 
-                self.queue.write_texture(
-                    wgpu::ImageCopyTexture {
-                        texture: &diffuse_texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d::ZERO,
-                        aspect: wgpu::TextureAspect::All,
-                    },
-                    &diffuse_rgba,
-                    wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * dimensions.0),
-                        rows_per_image: Some(dimensions.1),
-                    },
-                    texture_size,
-                );
-
-                let diffuse_texture_view =
-                    diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default());
-                let diffuse_sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-                    address_mode_u: wgpu::AddressMode::ClampToEdge,
-                    address_mode_v: wgpu::AddressMode::ClampToEdge,
-                    address_mode_w: wgpu::AddressMode::ClampToEdge,
-                    mag_filter: wgpu::FilterMode::Linear,
-                    min_filter: wgpu::FilterMode::Nearest,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
-                    ..Default::default()
-                });
+    pub fn redraw(&mut self, key: uuid::Uuid) {
+        self.renderer.redraw(key);
+        self.dirty = true;
+    }
 
-                let texture_bind_group_layout =
-                    self.device
-                        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                            label: Some("texture bind group layout"),
-                            entries: &[
-                                wgpu::BindGroupLayoutEntry {
-                                    binding: 0,
-                                    visibility: wgpu::ShaderStages::FRAGMENT,
-                                    ty: wgpu::BindingType::Texture {
-                                        sample_type: wgpu::TextureSampleType::Float {
-                                            filterable: true,
-                                        },
-                                        view_dimension: wgpu::TextureViewDimension::D2,
-                                        multisampled: false,
-                                    },
-                                    count: None,
-                                },
-                                wgpu::BindGroupLayoutEntry {
-                                    binding: 1,
-                                    visibility: wgpu::ShaderStages::FRAGMENT,
-                                    ty: wgpu::BindingType::Sampler(
-                                        wgpu::SamplerBindingType::Filtering,
-                                    ),
-                                    count: None,
-                                },
-                            ],
-                        });
+    /// Shows the crop handles of `image_id`, or none.
+    pub fn set_crop_image(&mut self, image_id: Option<uuid::Uuid>) {
+        self.renderer.set_crop_image(image_id);
+        self.dirty = true;
+    }
 
-                let diffuse_bind_group =
-                    self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: Some("diffuse_bind_group"),
-                        layout: &texture_bind_group_layout,
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 1,
-                                resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
-                            },
-                        ],
-                    });
-
-                let width_ration = texture_size.width as f32 / self.size.width as f32;
-                let height_ration = texture_size.height as f32 / self.size.height as f32;
+    /// Shows `loupe`, or none. Returns whether that changed anything.
+    pub fn set_loupe(&mut self, loupe: Option<Loupe>) -> bool {
+        let changed = self.renderer.set_loupe(loupe);
+        self.dirty |= changed;
+        changed
+    }
 
-                let vertices = vec![
-                    Vertex {
-                        position: [image.position[0], image.position[1], 0.],
-                        texture_coordinates: [0., 0.],
-                    }, // A
-                    Vertex {
-                        position: [image.position[0] + width_ration, image.position[1], 0.0],
-                        texture_coordinates: [1.0, 0.0],
-                    }, // B
-                    Vertex {
-                        position: [image.position[0], image.position[1] - height_ration, 0.0],
-                        texture_coordinates: [0.0, 1.0],
-                    }, // D
-                    Vertex {
-                        position: [image.position[0] + width_ration, image.position[1], 0.0],
-                        texture_coordinates: [1.0, 0.0],
-                    }, // B
-                    Vertex {
-                        position: [
-                            image.position[0] + width_ration,
-                            image.position[1] - height_ration,
-                            0.0,
-                        ],
-                        texture_coordinates: [1.0, 1.0],
-                    }, // C
-                    Vertex {
-                        position: [image.position[0], image.position[1] - height_ration, 0.0],
-                        texture_coordinates: [0.0, 1.0],
-                    }, // D
-                ];
+    pub fn value_mode(&self) -> ValueMode {
+        self.value_mode
+    }
 
-                let vertex_buffer =
-                    self.device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some("Vertex Buffer"),
-                            contents: bytemuck::cast_slice(&vertices),
-                            usage: wgpu::BufferUsages::VERTEX,
-                        });
+    pub fn set_value_mode(&mut self, mode: ValueMode) {
+        self.value_mode = mode;
+        self.dirty = true;
+    }
 
-                let shader = self
-                    .device
-                    .create_shader_module(wgpu::ShaderModuleDescriptor {
-                        label: Some("Shader"),
-                        source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-                    });
+    pub fn set_vision(&mut self, vision: Vision) {
+        self.renderer.set_vision(vision);
+        self.dirty = true;
+    }
 
-                let render_pipeline_layout =
-                    self.device
-                        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                            label: Some("Render Pipeline Layout"),
-                            bind_group_layouts: &[&texture_bind_group_layout],
-                            push_constant_ranges: &[],
-                        });
+    pub fn set_found(&mut self, found: Option<HashSet<uuid::Uuid>>) {
+        self.dirty |= self.renderer.set_found(found);
+    }
 
-                let render_pipeline =
-                    self.device
-                        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                            label: Some("Render Pipeline"),
-                            layout: Some(&render_pipeline_layout),
-                            vertex: wgpu::VertexState {
-                                module: &shader,
-                                entry_point: "vs_main",
-                                buffers: &[Vertex::desc()],
-                            },
-                            fragment: Some(wgpu::FragmentState {
-                                module: &shader,
-                                entry_point: "fs_main",
-                                targets: &[Some(wgpu::ColorTargetState {
-                                    format: self.config.format,
-                                    blend: Some(wgpu::BlendState::REPLACE),
-                                    write_mask: wgpu::ColorWrites::ALL,
-                                })],
-                            }),
-                            primitive: wgpu::PrimitiveState {
-                                topology: wgpu::PrimitiveTopology::TriangleList,
-                                strip_index_format: None,
-                                front_face: wgpu::FrontFace::Cw,
-                                cull_mode: Some(wgpu::Face::Back),
-                                unclipped_depth: false,
-                                polygon_mode: wgpu::PolygonMode::Fill,
-                                conservative: false,
-                            },
-                            depth_stencil: None,
-                            multisample: wgpu::MultisampleState {
-                                count: 1,
-                                mask: !0,
-                                alpha_to_coverage_enabled: false,
-                            },
-                            multiview: None,
-                        });
+    pub fn render_frame(&mut self, frame_id: uuid::Uuid) -> Option<image::RgbaImage> {
+        self.renderer.render_frame(frame_id)
+    }
 
-                let component = GraphicComponent {
-                    render_pipeline,
-                    vertex_buffer,
-                    diffuse_bind_group,
-                    vertices,
-                };
+    /// Moves the camera so the whole board fits in the window.
+    pub fn zoom_to_fit(&mut self) {
+        let bounds = self.renderer.library().bounds();
+        self.zoom_to_region(bounds);
+    }
 
-                self.context.insert(image_id, component);
-            }
-            _ => {}
+    /// Moves the camera so the images `keys` fit in the window.
+    pub fn zoom_to(&mut self, keys: &[uuid::Uuid]) {
+        let bounds = self.renderer.library().bounds_of(keys);
+        self.zoom_to_region(bounds);
+    }
+
+    fn zoom_to_region(&mut self, region: Option<[f32; 4]>) {
+        if let Some([x, y, width, height]) = region {
+            self.renderer.camera = Camera::fit(
+                [
+                    x - SPACING,
+                    y - SPACING,
+                    width + 2. * SPACING,
+                    height + 2. * SPACING,
+                ],
+                self.size.width,
+                self.size.height,
+            );
+            self.dirty = true;
         }
     }
 
+    /// Board coordinates under a point of the window.
+    pub fn screen_to_world(&self, position: winit::dpi::PhysicalPosition<f64>) -> [f32; 2] {
+        self.renderer.camera.screen_to_world(
+            [position.x as f32, position.y as f32],
+            self.size.width,
+            self.size.height,
+        )
+    }
+
     pub fn window(&self) -> &Window {
         &self.window
     }
@@ -357,11 +954,22 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            self.surface.configure(&self.renderer.device, &self.config);
+            self.dirty = true;
         }
     }
 
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
+    /// Flags the board as changed so the next loop iteration renders a frame.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns whether a frame is pending and clears the flag.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    pub fn input(&mut self, _event: &WindowEvent) -> bool {
         false
     }
 
@@ -373,35 +981,31 @@ impl State {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.clear_color),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-            for (_, component) in &self.context {
-                render_pass.set_pipeline(&component.render_pipeline);
-                render_pass.set_bind_group(0, &component.diffuse_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, component.vertex_buffer.slice(..));
-                render_pass.draw(0..component.vertices.len() as u32, 0..1);
-            }
-        }
+        let mut encoder =
+            self.renderer
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                });
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        // The handles and the loupe follow the zoom, which changes without
+        // notice.
+        self.renderer.draw_crop_handles();
+        self.renderer.draw_loupe();
+        self.renderer.encode_post(
+            &mut encoder,
+            &view,
+            &self.renderer.camera,
+            self.size.width,
+            self.size.height,
+            self.value_mode,
+            true,
+            &mut self.post_target,
+        );
+
+        self.renderer
+            .queue
+            .submit(std::iter::once(encoder.finish()));
         output.present();
         Ok(())
     }
@@ -0,0 +1,140 @@
+use super::{Camera, Renderer, ValueMode};
+
+impl Renderer {
+    /// Largest width or height an offscreen render may have on this device.
+    pub fn max_render_size(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    /// Renders the `[x, y, width, height]` board region into an image of
+    /// `width` x `height` pixels and reads it back from the GPU.
+    pub fn render_region(&self, region: [f32; 4], width: u32, height: u32) -> image::RgbaImage {
+        let texture_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("offscreen_texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Rows copied out of a texture must be aligned, the padding is
+        // stripped again once the buffer is mapped.
+        let unpadded_bytes_per_row = 4 * width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+
+        // Value checks, crop handles and the loupe are for the screen, a
+        // simulated vision shows in renders too.
+        let camera = Camera::fit(region, width, height);
+        self.encode_post(
+            &mut encoder,
+            &view,
+            &camera,
+            width,
+            height,
+            ValueMode::Off,
+            false,
+            &mut None,
+        );
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture_size,
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("offscreen buffer was dropped before being mapped")
+            .expect("cannot map offscreen buffer");
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        output_buffer.unmap();
+
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .expect("offscreen buffer has the size of the render")
+    }
+
+    /// Renders the body of the frame `frame_id`, its contents shown even if
+    /// it is collapsed, at one pixel per board unit when the device allows.
+    pub fn render_frame(&mut self, frame_id: uuid::Uuid) -> Option<image::RgbaImage> {
+        let frame = self.library.get_frame_mut(&frame_id)?;
+        let collapsed = std::mem::replace(&mut frame.collapsed, false);
+        let region = [
+            frame.position[0],
+            frame.position[1],
+            frame.size[0],
+            frame.size[1],
+        ];
+        self.draw_frame(frame_id);
+
+        let shrink = (self.max_render_size() as f32 / region[2].max(region[3])).min(1.);
+        let width = ((region[2] * shrink).round() as u32).max(1);
+        let height = ((region[3] * shrink).round() as u32).max(1);
+        let render = self.render_region(region, width, height);
+
+        if let Some(frame) = self.library.get_frame_mut(&frame_id) {
+            frame.collapsed = collapsed;
+        }
+        self.draw_frame(frame_id);
+        Some(render)
+    }
+}
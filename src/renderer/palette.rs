@@ -0,0 +1,58 @@
+//! Extracted palettes, a row of swatches under their image.
+
+use std::collections::HashSet;
+
+use super::{
+    vector::{self, Panel},
+    Renderer,
+};
+
+impl Renderer {
+    /// Builds the swatches of `image_id` again, or drops them when it has no
+    /// palette.
+    pub fn draw_palette(&mut self, image_id: uuid::Uuid) {
+        let Some((image, ([x, y], [width, height]))) = self
+            .library
+            .get(&image_id)
+            .and_then(|image| Some((image, image.palette_rect()?)))
+        else {
+            self.palette_components.remove(&image_id);
+            return;
+        };
+        let swatch = width / image.palette.len() as f32;
+        let panels: Vec<Panel> = image
+            .palette
+            .iter()
+            .enumerate()
+            .map(|(index, color)| Panel {
+                position: [x + index as f32 * swatch, y],
+                size: [swatch, height],
+                fill: Some(*color),
+                stroke: None,
+            })
+            .collect();
+        let world = self.library.world(&image_id);
+        match vector::panels(&self.device, &panels, world) {
+            Some(component) => {
+                self.palette_components.insert(image_id, component);
+            }
+            None => {
+                self.palette_components.remove(&image_id);
+            }
+        }
+    }
+
+    pub(super) fn encode_palettes<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        hidden: &HashSet<uuid::Uuid>,
+    ) {
+        render_pass.set_pipeline(&self.vector_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for (image_id, component) in &self.palette_components {
+            if !hidden.contains(image_id) {
+                component.encode(render_pass);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) texture_coordinates: vec2<f32>,
+};
+
+// One triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+  var out: VertexOutput;
+  let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+  out.texture_coordinates = corner;
+  out.clip_position = vec4<f32>(corner * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+  return out;
+}
+
+// Value modes, matching the constants of `value.rs`. Plain grayscale has
+// no steps to apply.
+const NOTAN: u32 = 2u;
+const THRESHOLD: u32 = 3u;
+
+struct Post {
+  // Rows of the vision simulation matrix, see `vision.rs`.
+  vision: mat3x3<f32>,
+  // Value mode, off when 0.
+  mode: u32,
+  // Bands of the notan, or values of the threshold.
+  levels: u32,
+}
+
+@group(0) @binding(0)
+var board_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var board_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> post: Post;
+
+fn to_srgb(linear: f32) -> f32 {
+  if linear <= 0.0031308 {
+    return linear * 12.92;
+  }
+  return 1.055 * pow(linear, 1.0 / 2.4) - 0.055;
+}
+
+fn to_linear(srgb: f32) -> f32 {
+  if srgb <= 0.04045 {
+    return srgb / 12.92;
+  }
+  return pow((srgb + 0.055) / 1.055, 2.4);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let sampled = textureSample(board_texture, board_sampler, in.texture_coordinates);
+  // The matrix holds rows, so the color multiplies it from the left.
+  let color = clamp(sampled.rgb * post.vision, vec3<f32>(0.0), vec3<f32>(1.0));
+  if post.mode == 0u {
+    return vec4<f32>(color, sampled.a);
+  }
+  // Lightness as the eye sees it, so the bands are even steps of value.
+  let value = clamp(to_srgb(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722))), 0.0, 1.0);
+  let levels = f32(max(post.levels, 2u));
+  let band = min(floor(value * levels), levels - 1.0);
+  var shown = value;
+  if post.mode == NOTAN {
+    // Every band keeps its middle value.
+    shown = (band + 0.5) / levels;
+  } else if post.mode == THRESHOLD {
+    // Black, white and mid gray in between.
+    shown = band / (levels - 1.0);
+  }
+  return vec4<f32>(vec3<f32>(to_linear(shown)), sampled.a);
+}
@@ -7,13 +7,21 @@ struct VertexOutput {
   @location(0) texture_coordinates: vec2<f32>,
 };
 
+// Maps board coordinates to clip space: (position - center) * scale.
+struct Camera {
+  center: vec2<f32>,
+  scale: vec2<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
   var out: VertexOutput;
   out.texture_coordinates = model.texture_coordinates;
-  out.clip_position = vec4<f32>(model.position, 1.0);
+  out.clip_position = vec4<f32>((model.position.xy - camera.center) * camera.scale, model.position.z, 1.0);
   return out;
 }
 
@@ -23,7 +31,52 @@ var texture_diffuse: texture_2d<f32>;
 var sample_diffuse: sampler; 
 
 
+// Color adjustments of the image, see `Adjustments`.
+struct Adjustments {
+  brightness: f32,
+  contrast: f32,
+  saturation: f32,
+  hue: f32,
+  gamma: f32,
+  invert: f32,
+  grayscale: f32,
+}
+@group(2) @binding(0)
+var<uniform> adjustments: Adjustments;
+
+fn to_srgb(linear: vec3<f32>) -> vec3<f32> {
+  let low = linear * 12.92;
+  let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+  return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Turns the color around the gray axis by `angle` radians.
+fn rotate_hue(color: vec3<f32>, angle: f32) -> vec3<f32> {
+  let axis = vec3<f32>(0.57735);
+  let c = cos(angle);
+  return color * c + cross(axis, color) * sin(angle) + axis * dot(axis, color) * (1.0 - c);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-  return textureSample(texture_diffuse, sample_diffuse, in.texture_coordinates);
+  let sampled = textureSample(texture_diffuse, sample_diffuse, in.texture_coordinates);
+  // Adjusted as the artist sees the values, in sRGB.
+  var color = to_srgb(sampled.rgb);
+  color = rotate_hue(color, adjustments.hue);
+  color = mix(vec3<f32>(luma(color)), color, adjustments.saturation);
+  color = (color - 0.5) * adjustments.contrast + 0.5 + adjustments.brightness;
+  color = pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / adjustments.gamma));
+  color = mix(color, vec3<f32>(luma(color)), adjustments.grayscale);
+  color = mix(color, 1.0 - color, adjustments.invert);
+  return vec4<f32>(to_linear(color), sampled.a);
 }
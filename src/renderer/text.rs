@@ -0,0 +1,475 @@
+//! Text drawing. Glyphs are rasterized on the CPU into a coverage atlas and
+//! drawn as quads tinted with the text color, background boxes sample a white
+//! patch of the same atlas so one pipeline draws both.
+
+use std::collections::{HashMap, HashSet};
+
+use ab_glyph::{point, Font, GlyphId};
+use wgpu::util::DeviceExt;
+
+use super::Renderer;
+use crate::reference::{
+    text::{self, Text},
+    transform::Transform,
+};
+
+const ATLAS_SIZE: u32 = 1024;
+/// Atlas pixels per board unit, so text stays sharp when zoomed in a little.
+const OVERSAMPLING: f32 = 2.;
+/// Side of the white patch in the corner of the atlas.
+const WHITE_PATCH: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    position: [f32; 3],
+    texture_coordinates: [f32; 2],
+    /// Linear color and alpha.
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+pub(super) struct TextComponent {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl TextComponent {
+    /// Quads of `text`, placed on the board by `world`. None when it draws
+    /// nothing.
+    pub(super) fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: &mut GlyphAtlas,
+        text: &Text,
+        world: &Transform,
+    ) -> Result<Option<Self>, AtlasFull> {
+        let mut vertices = vertices(atlas, queue, text)?;
+        for vertex in &mut vertices {
+            let [x, y] = world.apply([vertex.position[0], vertex.position[1]]);
+            vertex.position = [x, y, 0.];
+        }
+        if vertices.is_empty() {
+            return Ok(None);
+        }
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Ok(Some(Self {
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+        }))
+    }
+
+    pub(super) fn encode<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Where a rasterized glyph sits in the atlas and how it is placed relative
+/// to its origin, in board units.
+#[derive(Clone, Copy)]
+struct Slot {
+    uv: [f32; 4],
+    offset: [f32; 2],
+    size: [f32; 2],
+}
+
+/// The atlas ran out of room, it has to be cleared and every text drawn again.
+pub(super) struct AtlasFull;
+
+pub(super) struct GlyphAtlas {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// Slots by glyph and rasterized size, None for glyphs with no outline.
+    glyphs: HashMap<(GlyphId, u32), Option<Slot>>,
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    pub(super) fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let texture_size = wgpu::Extent3d {
+            width: ATLAS_SIZE,
+            height: ATLAS_SIZE,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("glyph_atlas"),
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &[255; (WHITE_PATCH * WHITE_PATCH) as usize],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(WHITE_PATCH),
+                rows_per_image: Some(WHITE_PATCH),
+            },
+            wgpu::Extent3d {
+                width: WHITE_PATCH,
+                height: WHITE_PATCH,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("glyph_atlas_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let mut atlas = Self {
+            texture,
+            bind_group,
+            glyphs: HashMap::new(),
+            cursor: [0, 0],
+            row_height: 0,
+        };
+        atlas.clear();
+        atlas
+    }
+
+    /// Forgets every glyph, the white patch stays.
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = [WHITE_PATCH + 1, 0];
+        self.row_height = WHITE_PATCH;
+    }
+
+    /// Texture coordinates of the middle of the white patch.
+    fn white(&self) -> [f32; 4] {
+        let middle = WHITE_PATCH as f32 / 2. / ATLAS_SIZE as f32;
+        [middle, middle, middle, middle]
+    }
+
+    /// Slot of `glyph` at `font_size`, rasterized and uploaded on first use.
+    fn glyph(
+        &mut self,
+        queue: &wgpu::Queue,
+        glyph: GlyphId,
+        font_size: f32,
+    ) -> Result<Option<Slot>, AtlasFull> {
+        let pixels = (font_size * OVERSAMPLING).round().max(1.) as u32;
+        if let Some(slot) = self.glyphs.get(&(glyph, pixels)) {
+            return Ok(*slot);
+        }
+
+        let outlined =
+            text::font().outline_glyph(glyph.with_scale_and_position(pixels as f32, point(0., 0.)));
+        let Some(outlined) = outlined else {
+            self.glyphs.insert((glyph, pixels), None);
+            return Ok(None);
+        };
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        if width == 0 || height == 0 || width >= ATLAS_SIZE || height >= ATLAS_SIZE {
+            self.glyphs.insert((glyph, pixels), None);
+            return Ok(None);
+        }
+
+        if self.cursor[0] + width > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height + 1];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + height > ATLAS_SIZE {
+            return Err(AtlasFull);
+        }
+
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            coverage[(y * width + x) as usize] = (c.clamp(0., 1.) * 255.).round() as u8;
+        });
+        let [x, y] = self.cursor;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &coverage,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // Glyphs are a pixel apart so filtering does not bleed between them.
+        self.cursor[0] += width + 1;
+        self.row_height = self.row_height.max(height);
+
+        let size = ATLAS_SIZE as f32;
+        let scale = pixels as f32 / font_size;
+        let slot = Slot {
+            uv: [
+                x as f32 / size,
+                y as f32 / size,
+                (x + width) as f32 / size,
+                (y + height) as f32 / size,
+            ],
+            offset: [bounds.min.x / scale, bounds.min.y / scale],
+            size: [width as f32 / scale, height as f32 / scale],
+        };
+        self.glyphs.insert((glyph, pixels), Some(slot));
+        Ok(Some(slot))
+    }
+}
+
+pub(super) fn pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Text Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("text.wgsl").into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Text Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[TextVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// sRGB color to the linear values the blending works with.
+pub(super) fn linear_color(color: [u8; 4]) -> [f32; 4] {
+    let channel = |value: u8| {
+        let value = value as f32 / 255.;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    [
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2]),
+        color[3] as f32 / 255.,
+    ]
+}
+
+/// Two triangles covering `size` from `position`, in the winding the image
+/// quads use.
+fn quad(
+    vertices: &mut Vec<TextVertex>,
+    position: [f32; 2],
+    size: [f32; 2],
+    uv: [f32; 4],
+    color: [f32; 4],
+) {
+    let [x, y] = position;
+    let [width, height] = size;
+    let [u0, v0, u1, v1] = uv;
+    let corner = |px: f32, py: f32, u: f32, v: f32| TextVertex {
+        position: [px, py, 0.],
+        texture_coordinates: [u, v],
+        color,
+    };
+    vertices.extend([
+        corner(x, y, u0, v0),
+        corner(x + width, y, u1, v0),
+        corner(x, y + height, u0, v1),
+        corner(x + width, y, u1, v0),
+        corner(x + width, y + height, u1, v1),
+        corner(x, y + height, u0, v1),
+    ]);
+}
+
+fn vertices(
+    atlas: &mut GlyphAtlas,
+    queue: &wgpu::Queue,
+    text: &Text,
+) -> Result<Vec<TextVertex>, AtlasFull> {
+    let mut vertices = Vec::new();
+    if let Some(background) = text.background {
+        let white = atlas.white();
+        quad(
+            &mut vertices,
+            text.position,
+            text.size(),
+            white,
+            linear_color(background),
+        );
+    }
+
+    let color = linear_color(text.color);
+    for (glyph, origin) in text.layout() {
+        if let Some(slot) = atlas.glyph(queue, glyph, text.font_size)? {
+            let position = [origin[0] + slot.offset[0], origin[1] + slot.offset[1]];
+            quad(&mut vertices, position, slot.size, slot.uv, color);
+        }
+    }
+    Ok(vertices)
+}
+
+impl Renderer {
+    pub fn add_text_to_library(&mut self, text: Text) -> uuid::Uuid {
+        let text_id = self.library.insert_text(text);
+        self.draw_text(text_id);
+        text_id
+    }
+
+    /// Uploads the glyphs of a text, after it was added or edited.
+    pub fn draw_text(&mut self, text_id: uuid::Uuid) {
+        if self.try_draw_text(text_id).is_err() {
+            self.redraw_glyphs();
+        }
+    }
+
+    /// Starts the atlas over with the glyphs still in use only.
+    pub(super) fn redraw_glyphs(&mut self) {
+        self.atlas.clear();
+        let text_ids: Vec<uuid::Uuid> = self.library.texts().map(|(id, _)| *id).collect();
+        for text_id in text_ids {
+            if self.try_draw_text(text_id).is_err() {
+                log::warn!("Text {} does not fit in the glyph atlas", text_id);
+            }
+        }
+        let frame_ids: Vec<uuid::Uuid> = self.library.frames().map(|(id, _)| *id).collect();
+        for frame_id in frame_ids {
+            if self.try_draw_frame(frame_id).is_err() {
+                log::warn!("Frame {} title does not fit in the glyph atlas", frame_id);
+            }
+        }
+        let chip_ids: Vec<uuid::Uuid> = self.library.chips().map(|(id, _)| *id).collect();
+        for chip_id in chip_ids {
+            if self.try_draw_chip(chip_id).is_err() {
+                log::warn!("Chip {} label does not fit in the glyph atlas", chip_id);
+            }
+        }
+    }
+
+    fn try_draw_text(&mut self, text_id: uuid::Uuid) -> Result<(), AtlasFull> {
+        let component = match self.library.get_text(&text_id) {
+            Some(text) => {
+                let world = self.library.world(&text_id);
+                TextComponent::new(&self.device, &self.queue, &mut self.atlas, text, &world)?
+            }
+            None => None,
+        };
+        match component {
+            Some(component) => self.text_components.insert(text_id, component),
+            None => self.text_components.remove(&text_id),
+        };
+        Ok(())
+    }
+
+    pub(super) fn encode_texts<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        hidden: &HashSet<uuid::Uuid>,
+    ) {
+        render_pass.set_pipeline(&self.text_pipeline);
+        render_pass.set_bind_group(0, &self.atlas.bind_group, &[]);
+        // The annotation pass had the camera at group 0.
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        for (text_id, component) in &self.text_components {
+            if !hidden.contains(text_id) {
+                component.encode(render_pass);
+            }
+        }
+        self.encode_frame_titles(render_pass);
+        self.encode_chip_labels(render_pass, hidden);
+    }
+}
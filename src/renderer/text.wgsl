@@ -0,0 +1,41 @@
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) texture_coordinates: vec2<f32>,
+  @location(2) color: vec4<f32>,
+}
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) texture_coordinates: vec2<f32>,
+  @location(1) color: vec4<f32>,
+};
+
+// Maps board coordinates to clip space: (position - center) * scale.
+struct Camera {
+  center: vec2<f32>,
+  scale: vec2<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+  var out: VertexOutput;
+  out.texture_coordinates = model.texture_coordinates;
+  out.color = model.color;
+  out.clip_position = vec4<f32>((model.position.xy - camera.center) * camera.scale, model.position.z, 1.0);
+  return out;
+}
+
+// Glyph coverage in the red channel, background boxes sample a white patch.
+@group(0) @binding(0)
+var texture_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var sample_atlas: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let coverage = textureSample(texture_atlas, sample_atlas, in.texture_coordinates).r;
+  return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,68 @@
+//! Value checks: the board shown in grayscale, as a notan of a few value
+//! bands or thresholded, by the post-process pass.
+
+use std::fmt;
+
+/// Modes, matching the constants of `post.wgsl`.
+const GRAYSCALE: u32 = 1;
+const NOTAN: u32 = 2;
+const THRESHOLD: u32 = 3;
+
+/// Notan bands the mode starts with, and the range `more_bands` goes
+/// through.
+const DEFAULT_BANDS: u32 = 5;
+const MAX_BANDS: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueMode {
+    Off,
+    /// Luminance only.
+    Grayscale,
+    /// Values posterized to this many bands, each shown at its middle.
+    Notan(u32),
+    /// Black and white, with mid gray when there are three values.
+    Threshold(u32),
+}
+
+impl ValueMode {
+    /// The mode after this one, back to `Off` after the last.
+    pub fn next(self) -> Self {
+        match self {
+            ValueMode::Off => ValueMode::Grayscale,
+            ValueMode::Grayscale => ValueMode::Notan(DEFAULT_BANDS),
+            ValueMode::Notan(_) => ValueMode::Threshold(2),
+            ValueMode::Threshold(2) => ValueMode::Threshold(3),
+            ValueMode::Threshold(_) => ValueMode::Off,
+        }
+    }
+
+    /// One more notan band, starting over at 2 after the most.
+    pub fn more_bands(self) -> Self {
+        match self {
+            ValueMode::Notan(bands) if bands < MAX_BANDS => ValueMode::Notan(bands + 1),
+            ValueMode::Notan(_) => ValueMode::Notan(2),
+            mode => mode,
+        }
+    }
+
+    /// Mode and levels as `post.wgsl` reads them.
+    pub(super) fn uniform(self) -> [u32; 2] {
+        match self {
+            ValueMode::Off => [0, 0],
+            ValueMode::Grayscale => [GRAYSCALE, 0],
+            ValueMode::Notan(bands) => [NOTAN, bands],
+            ValueMode::Threshold(values) => [THRESHOLD, values],
+        }
+    }
+}
+
+impl fmt::Display for ValueMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueMode::Off => write!(f, "colors"),
+            ValueMode::Grayscale => write!(f, "grayscale"),
+            ValueMode::Notan(bands) => write!(f, "notan of {} values", bands),
+            ValueMode::Threshold(values) => write!(f, "threshold to {} values", values),
+        }
+    }
+}
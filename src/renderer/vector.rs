@@ -0,0 +1,356 @@
+//! Annotation drawing. Every stroke, box and ellipse is a quad whose fragment
+//! coverage comes from the signed distance to the shape, which keeps the
+//! edges anti-aliased at any zoom without multisampling.
+
+use std::collections::HashSet;
+
+use wgpu::util::DeviceExt;
+
+use super::{text::linear_color, Renderer};
+use crate::reference::{
+    annotation::{Annotation, Shape},
+    transform::Transform,
+};
+
+/// Room left around a shape for its anti-aliased edge, in board units.
+const EDGE_MARGIN: f32 = 1.;
+
+/// Primitive kinds, matching the constants of `vector.wgsl`.
+const SEGMENT: u32 = 0;
+const BOX_OUTLINE: u32 = 1;
+const BOX_FILL: u32 = 2;
+const ELLIPSE_OUTLINE: u32 = 3;
+const ELLIPSE_FILL: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VectorVertex {
+    position: [f32; 2],
+    /// Position relative to the center of the primitive, along its axes.
+    local: [f32; 2],
+    /// Half size of the box or ellipse, half length of the segment.
+    extent: [f32; 2],
+    half_width: f32,
+    kind: u32,
+    /// Linear color and alpha.
+    color: [f32; 4],
+}
+
+impl VectorVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32,
+            4 => Uint32,
+            5 => Float32x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<VectorVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+pub(super) struct VectorComponent {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl VectorComponent {
+    fn new(device: &wgpu::Device, vertices: &[VectorVertex]) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vector Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Self {
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+        }
+    }
+
+    pub(super) fn encode<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// The camera is the only binding, at group 0.
+pub(super) fn pipeline(
+    device: &wgpu::Device,
+    camera_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Vector Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("vector.wgsl").into()),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Vector Pipeline Layout"),
+        bind_group_layouts: &[camera_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Vector Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[VectorVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// Builds the quads of one annotation or set of panels.
+struct Tessellator {
+    vertices: Vec<VectorVertex>,
+    origin: [f32; 2],
+    /// Places the shapes on the board once moved to `origin`.
+    world: Transform,
+}
+
+impl Tessellator {
+    /// Quad centered on `center` with its x axis along `axis`, a unit vector,
+    /// covering `reach` on each side.
+    #[allow(clippy::too_many_arguments)]
+    fn quad(
+        &mut self,
+        center: [f32; 2],
+        axis: [f32; 2],
+        reach: [f32; 2],
+        extent: [f32; 2],
+        half_width: f32,
+        kind: u32,
+        color: [f32; 4],
+    ) {
+        let center = [center[0] + self.origin[0], center[1] + self.origin[1]];
+        let corner = |sx: f32, sy: f32| {
+            let local = [sx * reach[0], sy * reach[1]];
+            VectorVertex {
+                position: self.world.apply([
+                    center[0] + local[0] * axis[0] - local[1] * axis[1],
+                    center[1] + local[0] * axis[1] + local[1] * axis[0],
+                ]),
+                local,
+                extent,
+                half_width,
+                kind,
+                color,
+            }
+        };
+        self.vertices.extend([
+            corner(-1., -1.),
+            corner(1., -1.),
+            corner(-1., 1.),
+            corner(1., -1.),
+            corner(1., 1.),
+            corner(-1., 1.),
+        ]);
+    }
+
+    fn segment(&mut self, from: [f32; 2], to: [f32; 2], half_width: f32, color: [f32; 4]) {
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let length = dx.hypot(dy);
+        let axis = if length > 0. {
+            [dx / length, dy / length]
+        } else {
+            [1., 0.]
+        };
+        let center = [(from[0] + to[0]) / 2., (from[1] + to[1]) / 2.];
+        let reach = half_width + EDGE_MARGIN;
+        self.quad(
+            center,
+            axis,
+            [length / 2. + reach, reach],
+            [length / 2., 0.],
+            half_width,
+            SEGMENT,
+            color,
+        );
+    }
+
+    /// Box or ellipse between the corners `from` and `to`, with an outline
+    /// of `stroke` color and `width`.
+    fn closed(
+        &mut self,
+        [from, to]: [[f32; 2]; 2],
+        fill: Option<[u8; 4]>,
+        stroke: Option<([u8; 4], f32)>,
+        [fill_kind, outline_kind]: [u32; 2],
+    ) {
+        let center = [(from[0] + to[0]) / 2., (from[1] + to[1]) / 2.];
+        // Flat ellipses would divide by zero in the shader.
+        let extent = [
+            ((to[0] - from[0]).abs() / 2.).max(0.01),
+            ((to[1] - from[1]).abs() / 2.).max(0.01),
+        ];
+        if let Some(fill) = fill {
+            let reach = [extent[0] + EDGE_MARGIN, extent[1] + EDGE_MARGIN];
+            self.quad(
+                center,
+                [1., 0.],
+                reach,
+                extent,
+                0.,
+                fill_kind,
+                linear_color(fill),
+            );
+        }
+        let Some((stroke, width)) = stroke else {
+            return;
+        };
+        let half_width = width / 2.;
+        let reach = [
+            extent[0] + half_width + EDGE_MARGIN,
+            extent[1] + half_width + EDGE_MARGIN,
+        ];
+        self.quad(
+            center,
+            [1., 0.],
+            reach,
+            extent,
+            half_width,
+            outline_kind,
+            linear_color(stroke),
+        );
+    }
+}
+
+fn vertices(annotation: &Annotation, origin: [f32; 2], world: Transform) -> Vec<VectorVertex> {
+    let mut tessellator = Tessellator {
+        vertices: Vec::new(),
+        origin,
+        world,
+    };
+    let half_width = annotation.width / 2.;
+    let stroke = linear_color(annotation.stroke);
+    match &annotation.shape {
+        Shape::Arrow { from, to } => {
+            tessellator.segment(*from, *to, half_width, stroke);
+            for side in annotation.arrow_head(*from, *to) {
+                tessellator.segment(side, *to, half_width, stroke);
+            }
+        }
+        Shape::Rectangle { from, to } => tessellator.closed(
+            [*from, *to],
+            annotation.fill,
+            Some((annotation.stroke, annotation.width)),
+            [BOX_FILL, BOX_OUTLINE],
+        ),
+        Shape::Ellipse { from, to } => tessellator.closed(
+            [*from, *to],
+            annotation.fill,
+            Some((annotation.stroke, annotation.width)),
+            [ELLIPSE_FILL, ELLIPSE_OUTLINE],
+        ),
+        Shape::Freehand { points } => match points[..] {
+            [] => {}
+            [point] => tessellator.segment(point, point, half_width, stroke),
+            _ => {
+                for pair in points.windows(2) {
+                    tessellator.segment(pair[0], pair[1], half_width, stroke);
+                }
+            }
+        },
+    }
+    tessellator.vertices
+}
+
+/// Axis aligned box on the board, for the shapes that are not annotations.
+pub(super) struct Panel {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub fill: Option<[u8; 4]>,
+    /// Outline color and width.
+    pub stroke: Option<([u8; 4], f32)>,
+}
+
+/// Quads of `panels`, placed on the board by `world`.
+pub(super) fn panels(
+    device: &wgpu::Device,
+    panels: &[Panel],
+    world: Transform,
+) -> Option<VectorComponent> {
+    let mut tessellator = Tessellator {
+        vertices: Vec::new(),
+        origin: [0., 0.],
+        world,
+    };
+    for panel in panels {
+        let [x, y] = panel.position;
+        let corners = [[x, y], [x + panel.size[0], y + panel.size[1]]];
+        tessellator.closed(corners, panel.fill, panel.stroke, [BOX_FILL, BOX_OUTLINE]);
+    }
+    (!tessellator.vertices.is_empty()).then(|| VectorComponent::new(device, &tessellator.vertices))
+}
+
+impl Renderer {
+    pub fn add_annotation_to_library(&mut self, annotation: Annotation) -> uuid::Uuid {
+        let annotation_id = self.library.insert_annotation(annotation);
+        self.draw_annotation(annotation_id);
+        annotation_id
+    }
+
+    /// Builds the quads of an annotation after it was added, edited or its
+    /// image moved, or drops them if it is gone.
+    pub fn draw_annotation(&mut self, annotation_id: uuid::Uuid) {
+        let Some(annotation) = self.library.get_annotation(&annotation_id) else {
+            self.vector_components.remove(&annotation_id);
+            return;
+        };
+        let origin = self.library.annotation_origin(annotation);
+        let world = self.library.world(&annotation_id);
+        let vertices = vertices(annotation, origin, world);
+        if vertices.is_empty() {
+            self.vector_components.remove(&annotation_id);
+            return;
+        }
+
+        self.vector_components
+            .insert(annotation_id, VectorComponent::new(&self.device, &vertices));
+    }
+
+    pub(super) fn encode_annotations<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        hidden: &HashSet<uuid::Uuid>,
+    ) {
+        render_pass.set_pipeline(&self.vector_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for (annotation_id, component) in &self.vector_components {
+            if !hidden.contains(annotation_id) {
+                component.encode(render_pass);
+            }
+        }
+    }
+}
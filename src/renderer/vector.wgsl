@@ -0,0 +1,73 @@
+struct VertexInput {
+  @location(0) position: vec2<f32>,
+  @location(1) local: vec2<f32>,
+  @location(2) extent: vec2<f32>,
+  @location(3) half_width: f32,
+  @location(4) kind: u32,
+  @location(5) color: vec4<f32>,
+}
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) local: vec2<f32>,
+  @location(1) @interpolate(flat) extent: vec2<f32>,
+  @location(2) @interpolate(flat) half_width: f32,
+  @location(3) @interpolate(flat) kind: u32,
+  @location(4) color: vec4<f32>,
+};
+
+// Maps board coordinates to clip space: (position - center) * scale.
+struct Camera {
+  center: vec2<f32>,
+  scale: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+  var out: VertexOutput;
+  out.local = model.local;
+  out.extent = model.extent;
+  out.half_width = model.half_width;
+  out.kind = model.kind;
+  out.color = model.color;
+  out.clip_position = vec4<f32>((model.position - camera.center) * camera.scale, 0.0, 1.0);
+  return out;
+}
+
+// Every primitive is a quad around a shape centered on `local` zero, the
+// fragment coverage comes from its signed distance in board units.
+const SEGMENT: u32 = 0u;
+const BOX_OUTLINE: u32 = 1u;
+const BOX_FILL: u32 = 2u;
+const ELLIPSE_OUTLINE: u32 = 3u;
+const ELLIPSE_FILL: u32 = 4u;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let p = in.local;
+  let r = in.extent;
+
+  // Segment along x of half length r.x, with round caps.
+  let segment = length(vec2<f32>(max(abs(p.x) - r.x, 0.0), p.y)) - in.half_width;
+
+  let b = abs(p) - r;
+  let rectangle = length(max(b, vec2<f32>(0.0))) + min(max(b.x, b.y), 0.0);
+
+  // First order distance to the ellipse of radii r.
+  let k = length(p / r);
+  let gradient = length(p / (r * r)) / max(k, 1e-6);
+  let ellipse = (k - 1.0) / max(gradient, 1e-6);
+
+  var distance = segment;
+  distance = select(distance, abs(rectangle) - in.half_width, in.kind == BOX_OUTLINE);
+  distance = select(distance, rectangle, in.kind == BOX_FILL);
+  distance = select(distance, abs(ellipse) - in.half_width, in.kind == ELLIPSE_OUTLINE);
+  distance = select(distance, ellipse, in.kind == ELLIPSE_FILL);
+
+  // One pixel wide edge whatever the zoom.
+  let coverage = clamp(0.5 - distance / max(fwidth(distance), 1e-6), 0.0, 1.0);
+  return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,76 @@
+//! Color vision deficiency simulation, applied to the whole board by the
+//! post-process pass.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Vision {
+    #[default]
+    Normal,
+    /// No long wavelength cones, reds look dark.
+    Protanopia,
+    /// No medium wavelength cones.
+    Deuteranopia,
+    /// No short wavelength cones, blues and yellows mix up.
+    Tritanopia,
+    /// No cones at all, only luminance is seen.
+    Achromatopsia,
+}
+
+impl Vision {
+    pub const ALL: [Vision; 5] = [
+        Vision::Normal,
+        Vision::Protanopia,
+        Vision::Deuteranopia,
+        Vision::Tritanopia,
+        Vision::Achromatopsia,
+    ];
+
+    /// Vision whose name starts with `name`, ignoring case.
+    pub fn find(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        Self::ALL
+            .into_iter()
+            .find(|vision| vision.to_string().starts_with(&name))
+    }
+
+    /// Rows of the matrix applied to linear RGB. The dichromacies are the
+    /// full severity matrices of Machado, Oliveira and Fernandes (2009).
+    pub(super) fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Vision::Normal => [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            Vision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Vision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Vision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+            Vision::Achromatopsia => [[0.2126, 0.7152, 0.0722]; 3],
+        }
+    }
+}
+
+impl fmt::Display for Vision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Vision::Normal => "normal",
+            Vision::Protanopia => "protanopia",
+            Vision::Deuteranopia => "deuteranopia",
+            Vision::Tritanopia => "tritanopia",
+            Vision::Achromatopsia => "achromatopsia",
+        };
+        write!(f, "{}", name)
+    }
+}
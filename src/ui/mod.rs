@@ -1,97 +1,1777 @@
+use std::path::{Path, PathBuf};
+
 use winit::{
-    event::{ElementState, Event, MouseButton, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode,
+        WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoopBuilder},
     window::WindowBuilder,
 };
 
+use prompt::{Prompt, PromptKind};
+use tool::{Drag, Tool};
+
+#[cfg(unix)]
+use crate::ipc;
 use crate::{
-    reference::{Image, Library},
-    renderer::State,
+    reference::{
+        adjust::Adjustments,
+        annotation::{self, Annotation},
+        board, chip, color, crop,
+        frame::{self, Frame},
+        group, import, palette, relink, similar,
+        text::Text,
+        Image, Library, Storage,
+    },
+    renderer::{Loupe, State, Vision},
+    watch::Watcher,
 };
 
-pub async fn run() {
-    let event_loop = EventLoop::new();
+mod prompt;
+mod tool;
+
+const TITLE: &str = "RustyRef";
+
+/// Font size change of `[` and `]`.
+const TEXT_SCALE_STEP: f32 = 1.25;
+const MIN_FONT_SIZE: f32 = 4.;
+const MAX_FONT_SIZE: f32 = 400.;
+
+/// Turn of `,` and `.` in degrees, and size change of `-` and `=`, for
+/// the selected groups.
+const GROUP_ROTATION_STEP: f32 = 15.;
+const GROUP_SCALE_STEP: f32 = 1.1;
+
+/// Distance from a crop handle, on screen, at which a press grabs it.
+const CROP_HANDLE_PIXELS: f32 = 8.;
+
+/// Pixels on a side of the eyedropper loupe, an odd number so one is in
+/// the middle.
+const LOUPE_PIXELS: u32 = 9;
+
+/// Smallest side of a frame drawn with the frame tool, on screen.
+const MIN_FRAME_PIXELS: f32 = 8.;
+
+/// Board written by Ctrl+S when nothing was opened from a board file.
+const DEFAULT_BOARD: &str = "board.rref";
+
+/// Events sent to the event loop from other threads.
+pub enum UserEvent {
+    #[cfg(unix)]
+    Ipc(ipc::Request),
+    FileChanged(PathBuf),
+}
+
+/// What the window starts with.
+pub struct Options {
+    pub paths: Vec<PathBuf>,
+    pub watch_folders: Vec<PathBuf>,
+    /// Tell the user when an import is already on the board.
+    pub warn_duplicates: bool,
+    /// Crop the borders of imported images, with this tolerance.
+    pub trim: Option<u8>,
+    /// Differing hash bits under which images count as similar.
+    pub similarity: u32,
+}
+
+/// Everything the event loop works on.
+struct App {
+    ctx: State,
+    watcher: Option<Watcher>,
+    /// Where Ctrl+S saves, the first board opened if any.
+    board_path: Option<PathBuf>,
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    modifiers: ModifiersState,
+    selected: Vec<uuid::Uuid>,
+    /// Text being typed, keys go to it instead of shortcuts while open.
+    prompt: Option<Prompt>,
+    /// Annotation tool, clicks draw instead of selecting while one is picked.
+    tool: Option<Tool>,
+    drag: Option<Drag>,
+    /// Image in crop mode, pressing on its handles moves them.
+    crop: Option<uuid::Uuid>,
+    /// Adjustments the images had when the adjust prompt opened, put back
+    /// by Escape.
+    adjusting: Vec<(uuid::Uuid, Adjustments)>,
+    /// Index of the annotation color in `tool::PALETTE`.
+    color: usize,
+    /// Opened on the first color picked, some systems drop what was copied
+    /// when it closes.
+    clipboard: Option<arboard::Clipboard>,
+    /// Asked once the board is on screen, so the placeholders show what is
+    /// missing.
+    offer_relink: bool,
+    warn_duplicates: bool,
+    trim: Option<u8>,
+    similarity: u32,
+    /// Whether this instance bound the socket, only then it removes it on
+    /// exit.
+    #[cfg(unix)]
+    listening: bool,
+}
+
+pub async fn run(options: Options) {
+    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
     let window = WindowBuilder::new()
-        .with_title("RustyRef")
+        .with_title(TITLE)
         .build(&event_loop)
         .unwrap();
 
-    let mut ctx = State::new(window).await;
-    let mut cursor_position: winit::dpi::PhysicalPosition<f64> = (0., 0.).into();
-    let mut hovered_image_id: uuid::Uuid = uuid::Uuid::new_v4();
+    let watcher = match Watcher::new(event_loop.create_proxy()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            eprintln!("Watch folders are disabled: {}", e);
+            None
+        }
+    };
+
+    let mut app = App {
+        ctx: State::new(window).await,
+        watcher,
+        board_path: None,
+        cursor_position: (0., 0.).into(),
+        modifiers: ModifiersState::empty(),
+        selected: Vec::new(),
+        prompt: None,
+        tool: None,
+        drag: None,
+        crop: None,
+        adjusting: Vec::new(),
+        color: 0,
+        clipboard: None,
+        offer_relink: false,
+        warn_duplicates: options.warn_duplicates,
+        trim: options.trim,
+        similarity: options.similarity,
+        #[cfg(unix)]
+        listening: false,
+    };
+
+    app.open_paths(&options.paths);
+    app.ctx.zoom_to_fit();
+    for folder in &options.watch_folders {
+        if let Err(e) = app.watch_folder(folder) {
+            eprintln!("{}: {}", folder.display(), e);
+        }
+    }
+    app.watch_board();
+    app.offer_relink = !app.ctx.renderer().library().missing().is_empty();
+
+    #[cfg(unix)]
+    match ipc::listen(event_loop.create_proxy()) {
+        Ok(()) => app.listening = true,
+        Err(e) => eprintln!("Not listening on {}: {}", ipc::socket_path().display(), e),
+    }
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
+        app.handle_event(event, control_flow);
+    });
+}
 
+impl App {
+    fn handle_event(&mut self, event: Event<UserEvent>, control_flow: &mut ControlFlow) {
         match event {
             Event::WindowEvent {
                 window_id,
                 ref event,
-            } if window_id == ctx.window().id() => {
-                if !ctx.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => ctx.resize(*physical_size),
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            ctx.resize(**new_inner_size)
-                        }
-                        WindowEvent::CursorMoved {
-                            device_id: _,
-                            position,
-                            modifiers: _,
-                        } => {
-                            cursor_position = *position;
-                            println!("{:?}", cursor_position);
+            } if window_id == self.ctx.window().id() && !self.ctx.input(event) => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(physical_size) => self.ctx.resize(*physical_size),
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    self.ctx.resize(**new_inner_size)
+                }
+                WindowEvent::ModifiersChanged(state) => self.modifiers = *state,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } => match self.prompt {
+                    Some(_) => self.prompt_key(*key),
+                    None => self.handle_key(*key),
+                },
+                WindowEvent::ReceivedCharacter(character) => {
+                    if let Some(prompt) = &mut self.prompt {
+                        prompt.type_character(*character);
+                        self.preview_adjustments();
+                        self.preview_search();
+                        self.show_prompt();
+                    }
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    self.cursor_position = *position;
+                    log::trace!("{:?}", self.cursor_position);
+                    self.show_loupe();
+                    self.drag_to_cursor();
+                }
+                WindowEvent::DroppedFile(path) => {
+                    let position = self.ctx.screen_to_world(self.cursor_position);
+                    match Image::open(path, position) {
+                        Ok(image) => {
+                            self.add_image(image);
                         }
-                        WindowEvent::HoveredFile(path_buff) => {
-                            let maybe_image = std::fs::read(path_buff.as_path());
-                            match maybe_image {
-                                Ok(image) => {
-                                    let goldorak = Image::new(
-                                        [
-                                            (cursor_position.x as f32 / ctx.size.width as f32) * 2.
-                                                - 1.,
-                                            (cursor_position.y as f32 / ctx.size.height as f32)
-                                                * -2.
-                                                + 1.,
-                                        ],
-                                        image,
-                                    );
-                                    let maybe_image_id = ctx.add_image_to_library(goldorak);
-                                    match maybe_image_id {
-                                        Some(image_id) => hovered_image_id = image_id,
-                                        _ => {}
-                                    }
+                        Err(e) => eprintln!("{}: {}", path.display(), e),
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } if self.tool.is_some() => self.start_drawing(),
+                WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    button: MouseButton::Left,
+                    ..
+                } => self.finish_drawing(),
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    let position = self.ctx.screen_to_world(self.cursor_position);
+                    if let Some((image_id, edges)) = self.crop_handle_at(position) {
+                        self.drag = Some(Drag::Crop { image_id, edges });
+                        return;
+                    }
+                    let library = self.ctx.renderer().library();
+                    let hit = library.hit_test(position);
+                    match hit {
+                        // Shift adds to the selection, or takes out.
+                        Some(key) if self.modifiers.shift() => {
+                            match self.selected.iter().position(|id| *id == key) {
+                                Some(index) => {
+                                    self.selected.remove(index);
                                 }
-                                _ => {}
+                                None => self.selected.push(key),
                             }
                         }
-                        WindowEvent::DroppedFile(_) => {
-                            ctx.draw(hovered_image_id);
+                        _ => self.selected = hit.into_iter().collect(),
+                    }
+                    if let Some(frame_id) = frame::title_at(library, position) {
+                        self.selected = vec![frame_id];
+                        self.drag = Some(Drag::MoveFrame {
+                            frame_id,
+                            last: position,
+                        });
+                    } else if let Some((group_id, group)) =
+                        hit.and_then(|id| Some((id, library.get_group(&id)?)))
+                    {
+                        if group::locked(library, &group_id) {
+                            println!("{} holds locked images, unlock them to move it", group.name);
+                        } else {
+                            self.drag = Some(Drag::MoveGroup {
+                                group_id,
+                                last: position,
+                            });
+                        }
+                    }
+                    let Some(key) = hit else {
+                        return;
+                    };
+                    if let Some(image) = library.get(&key) {
+                        describe(image);
+                    } else if let Some(text) = library.get_text(&key) {
+                        println!("text: {}", text.content);
+                    } else if let Some(chip) = library.get_chip(&key) {
+                        match chip.name.as_str() {
+                            "" => println!("chip: {}", color::describe(chip.color)),
+                            name => println!("{}: {}", name, color::describe(chip.color)),
                         }
-                        WindowEvent::MouseInput {
-                            device_id: _,
-                            state: _,
-                            button,
-                            modifiers: _,
-                        } if *button == MouseButton::Left => {}
-                        _ => (),
+                        let hex = color::hex(chip.color);
+                        self.copy(hex);
+                    } else if let Some(group) = library.get_group(&key) {
+                        let members = group::members(library, &key).len();
+                        println!("{}: group of {} items", group.name, members);
                     }
                 }
-            }
-            Event::RedrawRequested(window_id) if window_id == ctx.window().id() => {
-                ctx.update();
-                match ctx.render() {
+                _ => (),
+            },
+            Event::UserEvent(event) => match event {
+                #[cfg(unix)]
+                UserEvent::Ipc(request) => self.handle_request(request),
+                UserEvent::FileChanged(path) => {
+                    if let Some(watcher) = &mut self.watcher {
+                        watcher.changed(path);
+                    }
+                }
+            },
+            #[cfg(unix)]
+            Event::LoopDestroyed if self.listening => ipc::shutdown(),
+            Event::RedrawRequested(window_id) if window_id == self.ctx.window().id() => {
+                self.ctx.update();
+                match self.ctx.render() {
                     Ok(_) => {}
-                    Err(wgpu::SurfaceError::Lost) => ctx.resize(ctx.size),
+                    Err(wgpu::SurfaceError::Lost) => self.ctx.resize(self.ctx.size),
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     Err(e) => eprintln!("{:?}", e),
                 }
             }
             Event::MainEventsCleared => {
-                ctx.window().request_redraw();
+                let settled = self
+                    .watcher
+                    .as_mut()
+                    .map(Watcher::take_settled)
+                    .unwrap_or_default();
+                for path in settled {
+                    self.reload_or_import(path);
+                }
+                // Only go back to the GPU when something on screen changed, an
+                // idle board must not keep a core busy.
+                if self.ctx.take_dirty() {
+                    self.ctx.window().request_redraw();
+                }
+            }
+            Event::RedrawEventsCleared => {
+                if std::mem::take(&mut self.offer_relink) {
+                    self.relink_missing();
+                }
+                if let Some(deadline) = self.watcher.as_ref().and_then(Watcher::deadline) {
+                    *control_flow = ControlFlow::WaitUntil(deadline);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode) {
+        let modifiers = self.modifiers;
+        match key {
+            VirtualKeyCode::S if modifiers.ctrl() => {
+                let path = self.board_path.get_or_insert_with(|| DEFAULT_BOARD.into());
+                match board::save(self.ctx.renderer().library(), path) {
+                    Ok(()) => println!("Board saved to {}", path.display()),
+                    Err(e) => eprintln!("{}: {}", path.display(), e),
+                }
+            }
+            VirtualKeyCode::Home => self.ctx.zoom_to_fit(),
+            VirtualKeyCode::L if modifiers.ctrl() => self.relink_missing(),
+            VirtualKeyCode::L => {
+                for image_id in self.selected.clone() {
+                    self.toggle_storage(image_id);
+                }
+            }
+            VirtualKeyCode::A if modifiers.ctrl() && modifiers.shift() => self.collect_assets(),
+            VirtualKeyCode::I if modifiers.shift() && !self.selected.is_empty() => {
+                let text = self
+                    .first_selected()
+                    .map(|image| prompt::format_provenance(&image.metadata.provenance));
+                self.open_prompt(PromptKind::Provenance, text.unwrap_or_default());
+            }
+            VirtualKeyCode::I => {
+                let library = self.ctx.renderer().library();
+                for image in self.selected.iter().filter_map(|id| library.get(id)) {
+                    inspect(image);
+                }
+            }
+            VirtualKeyCode::F if modifiers.shift() => self.gather_similar(),
+            VirtualKeyCode::F => {
+                if let Some(image_id) = self.selected.first() {
+                    self.find_similar(*image_id);
+                }
+            }
+            VirtualKeyCode::Slash => self.open_prompt(PromptKind::Search, String::new()),
+            VirtualKeyCode::T if modifiers.shift() => {
+                let library = self.ctx.renderer().library();
+                let edited = self
+                    .selected
+                    .iter()
+                    .find_map(|id| library.get_text(id).map(|text| (*id, text)));
+                match edited {
+                    Some((text_id, text)) => {
+                        let kind = PromptKind::Text {
+                            text_id: Some(text_id),
+                            position: text.position,
+                        };
+                        let content = text.content.replace('\n', "\\n");
+                        self.open_prompt(kind, content);
+                    }
+                    None => {
+                        let position = self.ctx.screen_to_world(self.cursor_position);
+                        let kind = PromptKind::Text {
+                            text_id: None,
+                            position,
+                        };
+                        self.open_prompt(kind, String::new());
+                    }
+                }
+            }
+            VirtualKeyCode::LBracket => self.scale_texts(1. / TEXT_SCALE_STEP),
+            VirtualKeyCode::RBracket => self.scale_texts(TEXT_SCALE_STEP),
+            VirtualKeyCode::T if !self.selected.is_empty() => {
+                let text = self.first_selected().map(|image| image.tags.join(", "));
+                self.open_prompt(PromptKind::Tags, text.unwrap_or_default());
+            }
+            VirtualKeyCode::N if !self.selected.is_empty() => {
+                let text = self.first_selected().map(|image| image.note.clone());
+                self.open_prompt(PromptKind::Note, text.unwrap_or_default());
+            }
+            VirtualKeyCode::A => self.pick_tool(Tool::Arrow),
+            VirtualKeyCode::R => self.pick_tool(Tool::Rectangle),
+            VirtualKeyCode::E if modifiers.ctrl() && modifiers.shift() => {
+                let path = self
+                    .board_path
+                    .as_deref()
+                    .unwrap_or(Path::new(DEFAULT_BOARD));
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                let text = format!("{}-palette.gpl", file_stem(&name));
+                self.open_prompt(PromptKind::PaletteFile, text);
+            }
+            VirtualKeyCode::E if modifiers.ctrl() => self.export_selected(),
+            VirtualKeyCode::E => self.pick_tool(Tool::Ellipse),
+            VirtualKeyCode::D => self.pick_tool(Tool::Freehand),
+            VirtualKeyCode::P if modifiers.ctrl() && modifiers.shift() => self.clear_palettes(),
+            VirtualKeyCode::P if modifiers.shift() => self.extract_palettes(),
+            VirtualKeyCode::P => self.pick_tool(Tool::Eyedropper),
+            VirtualKeyCode::B => self.pick_tool(Tool::Frame),
+            VirtualKeyCode::F2 => {
+                let library = self.ctx.renderer().library();
+                let renamed = self.selected.iter().find_map(|id| {
+                    let frame = library.get_frame(id).map(|frame| &frame.name);
+                    let chip = library.get_chip(id).map(|chip| &chip.name);
+                    match (frame, chip) {
+                        (Some(name), _) => Some((PromptKind::FrameName(*id), name.clone())),
+                        (None, Some(name)) => Some((PromptKind::ChipName(*id), name.clone())),
+                        (None, None) => None,
+                    }
+                });
+                if let Some((kind, name)) = renamed {
+                    self.open_prompt(kind, name);
+                }
+            }
+            VirtualKeyCode::O => {
+                for frame_id in self.selected.clone() {
+                    if let Some(frame) = self.ctx.library_mut().get_frame_mut(&frame_id) {
+                        frame.collapsed = !frame.collapsed;
+                        self.ctx.redraw(frame_id);
+                    }
+                }
+            }
+            VirtualKeyCode::G if modifiers.ctrl() && modifiers.shift() => self.ungroup_selected(),
+            VirtualKeyCode::G if modifiers.ctrl() => self.group_selected(),
+            VirtualKeyCode::Comma => self.transform_groups(|library, group_id| {
+                group::rotate(library, group_id, -GROUP_ROTATION_STEP)
+            }),
+            VirtualKeyCode::Period => self.transform_groups(|library, group_id| {
+                group::rotate(library, group_id, GROUP_ROTATION_STEP)
+            }),
+            VirtualKeyCode::Minus => self.transform_groups(|library, group_id| {
+                group::scale(library, group_id, 1. / GROUP_SCALE_STEP)
+            }),
+            VirtualKeyCode::Equals => self.transform_groups(|library, group_id| {
+                group::scale(library, group_id, GROUP_SCALE_STEP)
+            }),
+            VirtualKeyCode::K if modifiers.ctrl() && modifiers.shift() => {
+                let count = self.clear_flags(|image| std::mem::take(&mut image.locked));
+                println!("Unlocked {} images", count);
+            }
+            VirtualKeyCode::K => self.toggle_locked(),
+            VirtualKeyCode::H if modifiers.ctrl() && modifiers.shift() => {
+                let count = self.clear_flags(|image| std::mem::take(&mut image.hidden));
+                println!("Showing {} hidden images", count);
+            }
+            VirtualKeyCode::H if modifiers.shift() => {
+                let hidden = hidden_outline(self.ctx.renderer().library());
+                if hidden.is_empty() {
+                    println!("No hidden images");
+                    return;
+                }
+                let library = self.ctx.renderer().library();
+                for (index, image) in hidden.iter().filter_map(|id| library.get(id)).enumerate() {
+                    print!("{}. ", index + 1);
+                    describe(image);
+                }
+                self.open_prompt(PromptKind::Reveal, String::new());
+            }
+            VirtualKeyCode::H => {
+                let mut count = 0;
+                for image_id in std::mem::take(&mut self.selected) {
+                    if let Some(image) = self.ctx.library_mut().get_mut(&image_id) {
+                        image.hidden = true;
+                        count += 1;
+                    }
+                }
+                self.ctx.mark_dirty();
+                println!("Hid {} images, Shift+H lists them", count);
+            }
+            VirtualKeyCode::X if modifiers.ctrl() => {
+                let tolerance = self.trim.unwrap_or(crop::DEFAULT_TOLERANCE);
+                let mut trimmed = 0;
+                for image_id in self.selected.clone() {
+                    if crop::trim(self.ctx.library_mut(), &image_id, tolerance) {
+                        self.ctx.redraw(image_id);
+                        trimmed += 1;
+                    }
+                }
+                println!("Trimmed {} images", trimmed);
+            }
+            VirtualKeyCode::X if modifiers.shift() => {
+                for image_id in self.selected.clone() {
+                    if self.ctx.renderer().library().get(&image_id).is_some() {
+                        crop::set(self.ctx.library_mut(), &image_id, None);
+                        self.ctx.redraw(image_id);
+                    }
+                }
+            }
+            VirtualKeyCode::X => self.toggle_crop(),
+            VirtualKeyCode::J if modifiers.shift() => {
+                for image_id in self.selected.clone() {
+                    self.set_adjustments(image_id, Adjustments::default());
+                }
+            }
+            VirtualKeyCode::J => {
+                let library = self.ctx.renderer().library();
+                self.adjusting = self
+                    .selected
+                    .iter()
+                    .filter_map(|id| library.get(id).map(|image| (*id, image.adjustments)))
+                    .collect();
+                if let Some((_, adjustments)) = self.adjusting.first() {
+                    println!(
+                        "Type adjustments as `contrast 1.2, hue -30, invert`: brightness, \
+                         contrast, saturation, hue, gamma, invert and grayscale"
+                    );
+                    self.open_prompt(PromptKind::Adjust, adjustments.to_string());
+                }
+            }
+            VirtualKeyCode::M => {
+                let current = self.ctx.renderer().vision();
+                for (index, vision) in Vision::ALL.iter().enumerate() {
+                    let marker = if *vision == current { "  (shown)" } else { "" };
+                    println!("{}. {}{}", index + 1, vision, marker);
+                }
+                self.open_prompt(PromptKind::Vision, String::new());
+            }
+            VirtualKeyCode::V => {
+                let mode = self.ctx.value_mode();
+                let mode = if modifiers.shift() {
+                    mode.more_bands()
+                } else {
+                    mode.next()
+                };
+                self.ctx.set_value_mode(mode);
+                println!("Showing the board in {}", mode);
+            }
+            VirtualKeyCode::Tab => self.cycle_frames(modifiers.shift()),
+            VirtualKeyCode::Key1
+            | VirtualKeyCode::Key2
+            | VirtualKeyCode::Key3
+            | VirtualKeyCode::Key4
+            | VirtualKeyCode::Key5
+            | VirtualKeyCode::Key6
+            | VirtualKeyCode::Key7
+            | VirtualKeyCode::Key8
+            | VirtualKeyCode::Key9 => {
+                self.jump_to_frame(key as usize - VirtualKeyCode::Key1 as usize)
+            }
+            VirtualKeyCode::Escape if self.crop.is_some() => self.toggle_crop(),
+            VirtualKeyCode::Escape if self.tool.is_some() => {
+                self.tool = None;
+                self.show_loupe();
+                println!("Select mode");
+            }
+            VirtualKeyCode::C if modifiers.shift() => self.lay_out_chips(),
+            VirtualKeyCode::C => {
+                self.color = (self.color + 1) % tool::PALETTE.len();
+                let [r, g, b, _] = tool::PALETTE[self.color];
+                println!("Annotation color #{:02x}{:02x}{:02x}", r, g, b);
             }
             _ => (),
         }
+    }
+
+    /// Enters crop mode on the selected image, or leaves it.
+    fn toggle_crop(&mut self) {
+        let library = self.ctx.renderer().library();
+        self.crop = match self.crop {
+            Some(_) => {
+                println!("Crop mode off");
+                None
+            }
+            None => {
+                let image_id = self
+                    .selected
+                    .iter()
+                    .copied()
+                    .find(|id| library.get(id).is_some());
+                if image_id.is_some() {
+                    println!("Crop mode: drag the handles, Shift+X resets, X or Escape ends");
+                }
+                image_id
+            }
+        };
+        self.ctx.set_crop_image(self.crop);
+    }
+
+    /// Crop handle under the board point `position`, as its image and the
+    /// edges it moves.
+    fn crop_handle_at(&self, position: [f32; 2]) -> Option<(uuid::Uuid, [bool; 4])> {
+        let image_id = self.crop?;
+        let library = self.ctx.renderer().library();
+        let image = library.get(&image_id)?;
+        let local = library.to_local(&image_id, position);
+        let point = [local[0] - image.position[0], local[1] - image.position[1]];
+        let zoom = self.ctx.renderer().camera.zoom * library.world(&image_id).scale();
+        let reach = CROP_HANDLE_PIXELS / zoom;
+        crop::HANDLES
+            .into_iter()
+            .find(|edges| {
+                let [x, y] = crop::handle_position(*edges, image.size());
+                (point[0] - x).abs() <= reach && (point[1] - y).abs() <= reach
+            })
+            .map(|edges| (image_id, edges))
+    }
+
+    /// Applies the adjustments typed so far to the images being adjusted,
+    /// while they read as valid.
+    fn preview_adjustments(&mut self) {
+        let Some(prompt) = self
+            .prompt
+            .as_ref()
+            .filter(|prompt| prompt.kind == PromptKind::Adjust)
+        else {
+            return;
+        };
+        let Ok(adjustments) = Adjustments::default().parse(&prompt.text) else {
+            return;
+        };
+        for (image_id, _) in self.adjusting.clone() {
+            self.set_adjustments(image_id, adjustments);
+        }
+    }
+
+    /// Leaves out the images not matching the search as it is typed.
+    fn preview_search(&mut self) {
+        let Some(prompt) = self
+            .prompt
+            .as_ref()
+            .filter(|prompt| prompt.kind == PromptKind::Search)
+        else {
+            return;
+        };
+        let found = match prompt.text.trim() {
+            "" => None,
+            query => Some(
+                self.ctx
+                    .renderer()
+                    .library()
+                    .search(query)
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        self.ctx.set_found(found);
+    }
+
+    fn set_adjustments(&mut self, image_id: uuid::Uuid, adjustments: Adjustments) {
+        if let Some(image) = self.ctx.library_mut().get_mut(&image_id) {
+            image.adjustments = adjustments;
+            self.ctx.draw(image_id);
+        }
+    }
+
+    /// Grows or shrinks the selected texts.
+    fn scale_texts(&mut self, factor: f32) {
+        for text_id in self.selected.clone() {
+            let Some(text) = self.ctx.library_mut().get_text_mut(&text_id) else {
+                continue;
+            };
+            text.font_size = (text.font_size * factor).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+            self.ctx.draw_text(text_id);
+        }
+    }
+
+    /// Locks the selected images, or unlocks them if they all are. Locked
+    /// images drop out of the selection.
+    fn toggle_locked(&mut self) {
+        let library = self.ctx.library_mut();
+        let images: Vec<uuid::Uuid> = self
+            .selected
+            .iter()
+            .copied()
+            .filter(|id| library.get(id).is_some())
+            .collect();
+        let lock = images
+            .iter()
+            .any(|id| library.get(id).is_some_and(|image| !image.locked));
+        for image_id in &images {
+            if let Some(image) = library.get_mut(image_id) {
+                image.locked = lock;
+            }
+        }
+        if lock {
+            self.selected.retain(|id| !images.contains(id));
+            println!("Locked {} images, Ctrl+Shift+K unlocks all", images.len());
+        } else {
+            println!("Unlocked {} images", images.len());
+        }
+    }
+
+    /// Clears a flag of every image through `clear`, which returns whether
+    /// it was set. Returns how many were.
+    fn clear_flags(&mut self, clear: impl Fn(&mut Image) -> bool) -> usize {
+        let library = self.ctx.library_mut();
+        let image_ids: Vec<uuid::Uuid> = library.keys().copied().collect();
+        let mut count = 0;
+        for image_id in image_ids {
+            if library.get_mut(&image_id).is_some_and(&clear) {
+                count += 1;
+            }
+        }
+        self.ctx.mark_dirty();
+        count
+    }
+
+    /// Selected images, or every image when nothing is selected.
+    fn selected_or_all_images(&self) -> Vec<uuid::Uuid> {
+        let library = self.ctx.renderer().library();
+        let selected: Vec<uuid::Uuid> = self
+            .selected
+            .iter()
+            .filter(|id| library.get(id).is_some())
+            .copied()
+            .collect();
+        if self.selected.is_empty() {
+            library.keys().copied().collect()
+        } else {
+            selected
+        }
+    }
+
+    /// Extracts the dominant colors of the selected images, or of all of
+    /// them, and shows them under each.
+    fn extract_palettes(&mut self) {
+        let image_ids = self.selected_or_all_images();
+        palette::extract_images(self.ctx.library_mut(), &image_ids, palette::DEFAULT_COLORS);
+        for image_id in image_ids {
+            let Some(image) = self.ctx.renderer().library().get(&image_id) else {
+                continue;
+            };
+            let hexes: Vec<String> = image.palette.iter().map(|c| color::hex(*c)).collect();
+            if !hexes.is_empty() {
+                println!("{}  {}", image_id, hexes.join(" "));
+            }
+            self.ctx.redraw(image_id);
+        }
+    }
+
+    /// Writes the colors of the palettes of the selected images, or of all
+    /// of them, to `file_name` next to the board.
+    fn export_palette(&mut self, file_name: &str) {
+        let path = self.board_path.get_or_insert_with(|| DEFAULT_BOARD.into());
+        let directory = match board::board_directory(path) {
+            Ok(directory) => directory,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return;
+            }
+        };
+        let output = directory.join(file_name);
+        let name = output
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let library = self.ctx.renderer().library();
+        let mut colors: Vec<[u8; 4]> = Vec::new();
+        for image_id in self.selected_or_all_images() {
+            let palette = library.get(&image_id).map(|image| &image.palette);
+            for color in palette.into_iter().flatten() {
+                if !colors.contains(color) {
+                    colors.push(*color);
+                }
+            }
+        }
+        if colors.is_empty() {
+            println!("No palette to export, Shift+P extracts them");
+            return;
+        }
+        match palette::export(&output, &name, &colors) {
+            Ok(format) => println!(
+                "Exported {} colors to {} as {}",
+                colors.len(),
+                output.display(),
+                format
+            ),
+            Err(e) => eprintln!("{}: {}", output.display(), e),
+        }
+    }
+
+    fn clear_palettes(&mut self) {
+        let mut count = 0;
+        for image_id in self.selected_or_all_images() {
+            if let Some(image) = self.ctx.library_mut().get_mut(&image_id) {
+                if !image.palette.is_empty() {
+                    image.palette.clear();
+                    count += 1;
+                }
+            }
+            self.ctx.redraw(image_id);
+        }
+        println!("Cleared {} palettes", count);
+    }
+
+    /// Groups the selected items, the group is selected instead.
+    fn group_selected(&mut self) {
+        match group::group(self.ctx.library_mut(), None, &self.selected) {
+            Ok(group_id) => {
+                if let Some(group) = self.ctx.renderer().library().get_group(&group_id) {
+                    println!("{}: group of {} items", group.name, self.selected.len());
+                }
+                self.selected = vec![group_id];
+            }
+            Err(e) => eprintln!("Cannot group: {}", e),
+        }
+    }
+
+    /// Dissolves the selected groups and selects what they held.
+    fn ungroup_selected(&mut self) {
+        let mut children = Vec::new();
+        for key in self.selected.clone() {
+            if self.ctx.renderer().library().get_group(&key).is_none() {
+                continue;
+            }
+            match group::ungroup(self.ctx.library_mut(), &key) {
+                Ok(ungrouped) => children.extend(ungrouped),
+                Err(e) => eprintln!("Cannot ungroup: {}", e),
+            }
+        }
+        if !children.is_empty() {
+            self.selected = children;
+        }
+    }
+
+    /// Changes the selected groups through `change` and draws them again.
+    fn transform_groups(&mut self, change: impl Fn(&mut Library, &uuid::Uuid)) {
+        for key in self.selected.clone() {
+            let library = self.ctx.renderer().library();
+            let Some(group) = library.get_group(&key) else {
+                continue;
+            };
+            if group::locked(library, &key) {
+                println!(
+                    "{} holds locked images, unlock them to change it",
+                    group.name
+                );
+            } else {
+                change(self.ctx.library_mut(), &key);
+                self.ctx.redraw(key);
+            }
+        }
+    }
+
+    /// Picks `tool`, or goes back to selecting if it was already picked.
+    fn pick_tool(&mut self, tool: Tool) {
+        if self.tool == Some(tool) {
+            self.tool = None;
+            println!("Select mode");
+        } else if tool == Tool::Eyedropper {
+            self.tool = Some(tool);
+            println!("Eyedropper, click to pick a color, Escape to stop");
+        } else {
+            self.tool = Some(tool);
+            println!("{} tool, drag to draw, Escape to stop", tool.name());
+        }
+        self.show_loupe();
+    }
+
+    /// Shows the pixels under the cursor in the loupe and their color in the
+    /// window title while the eyedropper is picked.
+    fn show_loupe(&mut self) {
+        if self.tool != Some(Tool::Eyedropper) {
+            if self.ctx.set_loupe(None) {
+                self.show_prompt();
+            }
+            return;
+        }
+        let position = self.ctx.screen_to_world(self.cursor_position);
+        let library = self.ctx.renderer().library();
+        let picked = color::pick(library, position);
+        let loupe = picked.as_ref().map(|picked| Loupe {
+            position,
+            pixels: color::neighborhood(library, &picked.image_id, picked.pixel, LOUPE_PIXELS),
+        });
+        self.ctx.set_loupe(loupe);
+        match picked {
+            Some(picked) if self.prompt.is_none() => {
+                let title = format!("{} - {}", TITLE, color::describe(picked.color));
+                self.ctx.window().set_title(&title);
+            }
+            _ => self.show_prompt(),
+        }
+    }
+
+    /// Reads the color of the pixel under the cursor, copies it and adds it
+    /// to the swatches.
+    fn pick_color(&mut self) {
+        let position = self.ctx.screen_to_world(self.cursor_position);
+        let Some(picked) = color::pick(self.ctx.renderer().library(), position) else {
+            println!("No image under the cursor");
+            return;
+        };
+        println!(
+            "{} at {},{}",
+            color::describe(picked.color),
+            picked.pixel[0],
+            picked.pixel[1]
+        );
+        self.copy(color::hex(picked.color));
+        let library = self.ctx.library_mut();
+        if library.add_swatch(picked.color) {
+            println!("Swatch {} added", library.swatches.len());
+        }
+    }
+
+    /// Puts `text` on the system clipboard.
+    fn copy(&mut self, text: String) {
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => eprintln!("No clipboard: {}", e),
+            }
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            if let Err(e) = clipboard.set_text(text.clone()) {
+                eprintln!("{} not copied: {}", text, e);
+            }
+        }
+    }
+
+    /// Lays the palettes of the selected images as color chips, or the
+    /// picked swatches when no selected image has one.
+    fn lay_out_chips(&mut self) {
+        let library = self.ctx.renderer().library();
+        let mut colors: Vec<[u8; 4]> = Vec::new();
+        for image in self.selected.iter().filter_map(|id| library.get(id)) {
+            for color in &image.palette {
+                if !colors.contains(color) {
+                    colors.push(*color);
+                }
+            }
+        }
+        if colors.is_empty() {
+            colors = library.swatches.clone();
+        }
+        if colors.is_empty() {
+            println!("No palette or swatch to lay out, Shift+P extracts palettes");
+            return;
+        }
+        let position = library.next_position();
+        let chip_ids = chip::row(self.ctx.library_mut(), position, &colors);
+        for chip_id in &chip_ids {
+            self.ctx.redraw(*chip_id);
+        }
+        println!("Added {} color chips", chip_ids.len());
+        self.selected = chip_ids;
+    }
+
+    /// Starts drawing with the current tool under the cursor. Annotations
+    /// attach to the image there.
+    fn start_drawing(&mut self) {
+        let Some(tool) = self.tool else {
+            return;
+        };
+        let position = self.ctx.screen_to_world(self.cursor_position);
+        if tool == Tool::Eyedropper {
+            self.pick_color();
+            return;
+        }
+        if tool == Tool::Frame {
+            let count = self.ctx.renderer().library().frames().count();
+            let new_frame = Frame::new(format!("Frame {}", count + 1), position, [0., 0.]);
+            let frame_id = self.ctx.library_mut().insert_frame(new_frame);
+            self.ctx.redraw(frame_id);
+            self.drag = Some(Drag::NewFrame {
+                frame_id,
+                start: position,
+            });
+            return;
+        }
+
+        let library = self.ctx.renderer().library();
+        let attached_to = library.image_at(position);
+        let image =
+            attached_to.and_then(|image_id| Some((image_id, library.get(&image_id)?.position)));
+        let (origin, local) = match image {
+            Some((image_id, origin)) => (origin, library.to_local(&image_id, position)),
+            None => ([0., 0.], position),
+        };
+        let point = [local[0] - origin[0], local[1] - origin[1]];
+        let Some(shape) = tool.start(point) else {
+            return;
+        };
+
+        let mut annotation = Annotation::new(shape);
+        annotation.attached_to = attached_to;
+        annotation.stroke = tool::PALETTE[self.color];
+        annotation.width = tool::STROKE_PIXELS / self.ctx.renderer().camera.zoom;
+        if self.modifiers.shift() && annotation.shape.is_closed() {
+            let [r, g, b, _] = annotation.stroke;
+            annotation.fill = Some([r, g, b, tool::FILL_ALPHA]);
+        }
+        let annotation_id = self.ctx.add_annotation_to_library(annotation);
+        self.drag = Some(Drag::Annotation {
+            annotation_id,
+            origin,
+            last: point,
+        });
+    }
+
+    /// Follows the cursor with whatever is being dragged.
+    fn drag_to_cursor(&mut self) {
+        let position = self.ctx.screen_to_world(self.cursor_position);
+        let zoom = self.ctx.renderer().camera.zoom;
+        match &mut self.drag {
+            None => {}
+            Some(Drag::Annotation {
+                annotation_id,
+                origin,
+                last,
+            }) => {
+                let annotation_id = *annotation_id;
+                let library = self.ctx.library_mut();
+                let local = library.to_local(&annotation_id, position);
+                let point = [local[0] - origin[0], local[1] - origin[1]];
+                let Some(annotation) = library.get_annotation_mut(&annotation_id) else {
+                    return;
+                };
+                let moved = (point[0] - last[0]).hypot(point[1] - last[1]);
+                let freehand = matches!(annotation.shape, annotation::Shape::Freehand { .. });
+                if freehand && moved < tool::FREEHAND_STEP_PIXELS / zoom {
+                    return;
+                }
+                annotation.shape.drag_to(point);
+                *last = point;
+                self.ctx.draw_annotation(annotation_id);
+            }
+            Some(Drag::NewFrame { frame_id, start }) => {
+                let frame_id = *frame_id;
+                let start = *start;
+                if let Some(frame) = self.ctx.library_mut().get_frame_mut(&frame_id) {
+                    frame.position = [start[0].min(position[0]), start[1].min(position[1])];
+                    frame.size = [
+                        (position[0] - start[0]).abs(),
+                        (position[1] - start[1]).abs(),
+                    ];
+                }
+                self.ctx.redraw(frame_id);
+            }
+            Some(Drag::MoveFrame { frame_id, last }) => {
+                let frame_id = *frame_id;
+                let offset = [position[0] - last[0], position[1] - last[1]];
+                *last = position;
+                for item in frame::translate(self.ctx.library_mut(), &frame_id, offset) {
+                    self.ctx.redraw(item);
+                }
+            }
+            Some(Drag::MoveGroup { group_id, last }) => {
+                let group_id = *group_id;
+                let offset = [position[0] - last[0], position[1] - last[1]];
+                *last = position;
+                group::translate(self.ctx.library_mut(), &group_id, offset);
+                self.ctx.redraw(group_id);
+            }
+            Some(Drag::Crop { image_id, edges }) => {
+                let (image_id, edges) = (*image_id, *edges);
+                let library = self.ctx.library_mut();
+                let local = library.to_local(&image_id, position);
+                let Some(image) = library.get(&image_id) else {
+                    return;
+                };
+                // Pixel of the whole image under the cursor.
+                let [x, y, width, height] = image.crop_rect();
+                let point = [
+                    local[0] - image.position[0] + x as f32,
+                    local[1] - image.position[1] + y as f32,
+                ];
+                let cropped = crop::drag([x, y, width, height], image.full_size(), edges, point);
+                crop::set(library, &image_id, Some(cropped));
+                self.ctx.redraw(image_id);
+            }
+        }
+    }
+
+    /// Ends the drag. Annotations and frames drawn by a click without a drag
+    /// are dropped, a new frame takes what lies in it and asks for its name.
+    fn finish_drawing(&mut self) {
+        let zoom = self.ctx.renderer().camera.zoom;
+        match self.drag.take() {
+            Some(Drag::Annotation { annotation_id, .. }) => {
+                let library = self.ctx.library_mut();
+                let empty = library
+                    .get_annotation(&annotation_id)
+                    .is_some_and(|annotation| match annotation.shape.points()[..] {
+                        [from, to] => from == to,
+                        _ => false,
+                    });
+                if empty {
+                    library.remove_annotation(&annotation_id);
+                    self.ctx.draw_annotation(annotation_id);
+                } else {
+                    self.adopt(annotation_id);
+                }
+            }
+            Some(Drag::NewFrame { frame_id, .. }) => {
+                let library = self.ctx.library_mut();
+                let too_small = library
+                    .get_frame(&frame_id)
+                    .is_none_or(|frame| frame.size[0].min(frame.size[1]) * zoom < MIN_FRAME_PIXELS);
+                if too_small {
+                    library.remove_frame(&frame_id);
+                    self.ctx.redraw(frame_id);
+                    return;
+                }
+                frame::enclose(library, &frame_id);
+                let name = library
+                    .get_frame(&frame_id)
+                    .map(|frame| frame.name.clone())
+                    .unwrap_or_default();
+                self.ctx.redraw(frame_id);
+                self.selected = vec![frame_id];
+                self.open_prompt(PromptKind::FrameName(frame_id), name);
+            }
+            Some(Drag::MoveFrame { .. })
+            | Some(Drag::MoveGroup { .. })
+            | Some(Drag::Crop { .. })
+            | None => {}
+        }
+    }
+
+    /// Puts a new item in the frame it was placed in.
+    fn adopt(&mut self, item: uuid::Uuid) {
+        let library = self.ctx.library_mut();
+        let Some((position, size)) = library.rect(&item) else {
+            return;
+        };
+        let center = [position[0] + size[0] / 2., position[1] + size[1] / 2.];
+        if let Some(frame_id) = frame::frame_at(library, center) {
+            frame::adopt(library, &frame_id, item);
+        }
+    }
+
+    /// Selects and shows the frame `index` of the navigator.
+    fn jump_to_frame(&mut self, index: usize) {
+        let frames = frame::sorted(self.ctx.renderer().library());
+        let Some(frame_id) = frames.get(index) else {
+            return;
+        };
+        if let Some(frame) = self.ctx.renderer().library().get_frame(frame_id) {
+            println!("Frame {}/{}: {}", index + 1, frames.len(), frame.name);
+        }
+        self.selected = vec![*frame_id];
+        self.ctx.zoom_to(&[*frame_id]);
+    }
+
+    /// Goes to the next frame of the navigator, or the previous one.
+    fn cycle_frames(&mut self, backwards: bool) {
+        let frames = frame::sorted(self.ctx.renderer().library());
+        if frames.is_empty() {
+            println!("No frames");
+            return;
+        }
+        let current = self
+            .selected
+            .first()
+            .and_then(|id| frames.iter().position(|frame_id| frame_id == id));
+        let index = match (current, backwards) {
+            (Some(i), false) => (i + 1) % frames.len(),
+            (Some(i), true) => (i + frames.len() - 1) % frames.len(),
+            (None, false) => 0,
+            (None, true) => frames.len() - 1,
+        };
+        self.jump_to_frame(index);
+    }
+
+    /// Writes the selected frames, and the shown pixels of the selected
+    /// images, next to the board.
+    fn export_selected(&mut self) {
+        let path = self.board_path.get_or_insert_with(|| DEFAULT_BOARD.into());
+        let directory = match board::board_directory(path) {
+            Ok(directory) => directory,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return;
+            }
+        };
+        for frame_id in self.selected.clone() {
+            let Some(name) = self
+                .ctx
+                .renderer()
+                .library()
+                .get_frame(&frame_id)
+                .map(|frame| frame.name.clone())
+            else {
+                continue;
+            };
+            let output = directory.join(format!("{}.png", file_stem(&name)));
+            let Some(render) = self.ctx.render_frame(frame_id) else {
+                continue;
+            };
+            match render.save(&output) {
+                Ok(()) => println!("Exported {} to {}", name, output.display()),
+                Err(e) => eprintln!("{}: {}", output.display(), e),
+            }
+        }
+        let library = self.ctx.renderer().library();
+        for image_id in &self.selected {
+            let Some(image) = library
+                .get(image_id)
+                .filter(|image| image.missing.is_none())
+            else {
+                continue;
+            };
+            let name = image
+                .source
+                .as_ref()
+                .and_then(|source| source.path.file_stem())
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| image_id.to_string());
+            let output = directory.join(format!("{}-crop.png", file_stem(&name)));
+            match image.cropped().save(&output) {
+                Ok(()) => println!("Exported {}", output.display()),
+                Err(e) => eprintln!("{}: {}", output.display(), e),
+            }
+            if !image.palette.is_empty() {
+                let output = directory.join(format!("{}-palette.gpl", file_stem(&name)));
+                match palette::export(&output, &name, &image.palette) {
+                    Ok(_) => println!("Exported {}", output.display()),
+                    Err(e) => eprintln!("{}: {}", output.display(), e),
+                }
+            }
+        }
+    }
+
+    fn first_selected(&self) -> Option<&Image> {
+        let library = self.ctx.renderer().library();
+        self.selected.first().and_then(|id| library.get(id))
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind, text: String) {
+        self.prompt = Some(Prompt::new(kind, text));
+        self.show_prompt();
+    }
+
+    fn show_prompt(&self) {
+        let Some(prompt) = &self.prompt else {
+            self.ctx.window().set_title(TITLE);
+            return;
+        };
+        let status = match prompt.kind {
+            PromptKind::Search if !prompt.text.trim().is_empty() => {
+                let found = self.ctx.renderer().library().search(&prompt.text).len();
+                format!("({} found, Enter zooms, Shift+Enter selects)", found)
+            }
+            PromptKind::Search => String::new(),
+            PromptKind::Tags | PromptKind::Note | PromptKind::Provenance => {
+                format!("({} images, Enter applies)", self.selected.len())
+            }
+            PromptKind::Text { .. } => "(\\n for a new line, Enter applies)".to_string(),
+            PromptKind::FrameName(_) | PromptKind::ChipName(_) => "(Enter applies)".to_string(),
+            PromptKind::Reveal => {
+                let count = hidden_outline(self.ctx.renderer().library()).len();
+                format!("(1 to {}, Enter shows it)", count)
+            }
+            PromptKind::Vision => format!("(1 to {}, Enter shows it)", Vision::ALL.len()),
+            PromptKind::PaletteFile => {
+                "(.gpl, .ase, .json or a hex list, Enter exports)".to_string()
+            }
+            PromptKind::Adjust => match Adjustments::default().parse(&prompt.text) {
+                Ok(_) => format!(
+                    "({} images, Enter keeps, Escape undoes)",
+                    self.adjusting.len()
+                ),
+                Err(e) => format!("({})", e),
+            },
+        };
+        self.ctx.window().set_title(&prompt.title(&status));
+    }
+
+    fn prompt_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Escape => {
+                if self
+                    .prompt
+                    .take()
+                    .is_some_and(|prompt| prompt.kind == PromptKind::Adjust)
+                {
+                    for (image_id, adjustments) in std::mem::take(&mut self.adjusting) {
+                        self.set_adjustments(image_id, adjustments);
+                    }
+                }
+            }
+            VirtualKeyCode::Back => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.backspace();
+                }
+                self.preview_adjustments();
+                self.preview_search();
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt);
+                }
+            }
+            _ => return,
+        }
+        if self.prompt.is_none() {
+            self.ctx.set_found(None);
+        }
+        self.show_prompt();
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt) {
+        match prompt.kind {
+            PromptKind::Search => {
+                let found = self.ctx.renderer().library().search(&prompt.text);
+                println!("{} images match \"{}\"", found.len(), prompt.text);
+                if self.modifiers.shift() {
+                    self.selected = found;
+                } else if !found.is_empty() {
+                    self.ctx.zoom_to(&found);
+                }
+            }
+            PromptKind::Tags => {
+                let tags = prompt::parse_tags(&prompt.text);
+                for image_id in &self.selected {
+                    let tags = tags.clone();
+                    self.ctx
+                        .library_mut()
+                        .update(image_id, |image| image.tags = tags);
+                }
+            }
+            PromptKind::Text { text_id, position } => {
+                let content = prompt.text.replace("\\n", "\n");
+                match text_id {
+                    Some(text_id) => {
+                        if let Some(text) = self.ctx.library_mut().get_text_mut(&text_id) {
+                            text.content = content;
+                        }
+                        self.ctx.draw_text(text_id);
+                    }
+                    None if !content.trim().is_empty() => {
+                        let text_id = self.ctx.add_text_to_library(Text::new(position, content));
+                        self.adopt(text_id);
+                        self.selected = vec![text_id];
+                    }
+                    None => {}
+                }
+            }
+            PromptKind::FrameName(frame_id) => {
+                let name = prompt.text.trim();
+                if let Some(frame) = self.ctx.library_mut().get_frame_mut(&frame_id) {
+                    if !name.is_empty() {
+                        frame.name = name.to_string();
+                    }
+                }
+                self.ctx.redraw(frame_id);
+            }
+            PromptKind::ChipName(chip_id) => {
+                if let Some(chip) = self.ctx.library_mut().get_chip_mut(&chip_id) {
+                    chip.name = prompt.text.trim().to_string();
+                }
+                self.ctx.redraw(chip_id);
+            }
+            PromptKind::Reveal => {
+                let hidden = hidden_outline(self.ctx.renderer().library());
+                let picked = prompt
+                    .text
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| hidden.get(number.checked_sub(1)?));
+                let Some(image_id) = picked.copied() else {
+                    println!("No hidden image {}", prompt.text.trim());
+                    return;
+                };
+                if let Some(image) = self.ctx.library_mut().get_mut(&image_id) {
+                    image.hidden = false;
+                }
+                self.selected = vec![image_id];
+                self.ctx.zoom_to(&[image_id]);
+            }
+            PromptKind::Vision => {
+                let text = prompt.text.trim();
+                let picked = text
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| Vision::ALL.get(number.checked_sub(1)?).copied())
+                    .or_else(|| Vision::find(text));
+                match picked {
+                    Some(vision) => {
+                        self.ctx.set_vision(vision);
+                        println!("Simulating {} vision, also in exports", vision);
+                    }
+                    None => println!("No vision {}", text),
+                }
+            }
+            PromptKind::PaletteFile => match prompt.text.trim() {
+                "" => {}
+                file_name => self.export_palette(file_name),
+            },
+            PromptKind::Adjust => {
+                self.adjusting.clear();
+                if let Err(e) = Adjustments::default().parse(&prompt.text) {
+                    eprintln!("{}", e);
+                }
+            }
+            PromptKind::Provenance => {
+                let provenance = prompt::parse_provenance(&prompt.text);
+                for image_id in &self.selected {
+                    let provenance = provenance.clone();
+                    self.ctx
+                        .library_mut()
+                        .update(image_id, |image| image.metadata.provenance = provenance);
+                }
+            }
+            PromptKind::Note => {
+                for image_id in &self.selected {
+                    let note = prompt.text.trim().to_string();
+                    self.ctx
+                        .library_mut()
+                        .update(image_id, |image| image.note = note);
+                }
+            }
+        }
+    }
+
+    /// Opens the images, folders and boards given on the command line. The
+    /// first board is the one saving writes back to.
+    fn open_paths(&mut self, paths: &[PathBuf]) {
+        let paths = match import::expand_paths(paths) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+
+        for path in paths {
+            if import::is_board_path(&path) {
+                match board::load(&path) {
+                    Ok(library) => {
+                        self.ctx.extend_library(library);
+                        self.board_path.get_or_insert(path);
+                    }
+                    Err(e) => eprintln!("{}: {}", path.display(), e),
+                }
+            } else if let Err(e) = self.open_image(&path) {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn open_image(&mut self, path: &Path) -> image::ImageResult<Option<uuid::Uuid>> {
+        let position = self.ctx.renderer().library().next_position();
+        let image = Image::open(path, position)?;
+        Ok(self.add_image(image))
+    }
+
+    /// Puts an imported image on the board and follows its file.
+    fn add_image(&mut self, mut image: Image) -> Option<uuid::Uuid> {
+        if self.warn_duplicates {
+            let copies = self
+                .ctx
+                .renderer()
+                .library()
+                .find_by_hash(&image.hash)
+                .len();
+            if copies > 0 {
+                println!("Already on the board {} times", copies);
+            }
+        }
+
+        if let Some(tolerance) = self.trim {
+            image.crop = crop::content(&image.image, tolerance);
+        }
+        let image_id = self.ctx.add_image_to_library(image)?;
+        self.ctx.draw(image_id);
+        self.adopt(image_id);
+        self.watch_source(image_id);
+        Some(image_id)
+    }
+
+    /// Switches an image between embedded in the board and linked to its file.
+    fn toggle_storage(&mut self, image_id: uuid::Uuid) {
+        let Some(image) = self.ctx.library_mut().get_mut(&image_id) else {
+            return;
+        };
+        if image.source.is_none() {
+            println!("This image has no file to link to, it stays embedded");
+            return;
+        }
+        if image.missing.is_some() {
+            println!("The linked file is missing, relink it first");
+            return;
+        }
+        image.storage = match image.storage {
+            Storage::Embedded => Storage::Linked,
+            Storage::Linked => Storage::Embedded,
+        };
+        describe(image);
+    }
+
+    /// Shows `library`, loaded from `path`, instead of the current board.
+    /// Missing files are asked for once it is on screen.
+    fn replace_board(&mut self, library: Library, path: PathBuf) {
+        self.selected.clear();
+        self.drag = None;
+        self.prompt = None;
+        self.ctx.set_found(None);
+        self.adjusting.clear();
+        self.crop = None;
+        if let Some(watcher) = &mut self.watcher {
+            watcher.forget_files();
+        }
+        self.ctx.set_library(library);
+        self.ctx.zoom_to_fit();
+        self.watch_board();
+        self.offer_relink = !self.ctx.renderer().library().missing().is_empty();
+        self.board_path = Some(path);
+        self.show_prompt();
+    }
+
+    /// Asks for a folder to search for the missing linked files.
+    fn relink_missing(&mut self) {
+        let missing = self.ctx.renderer().library().missing().len();
+        if missing == 0 {
+            return;
+        }
+        let Some(folder) = rfd::FileDialog::new()
+            .set_title(format!("Locate {} missing files", missing))
+            .pick_folder()
+        else {
+            return;
+        };
+
+        match relink::relink(self.ctx.library_mut(), &folder) {
+            Ok(relinked) => {
+                println!("Relinked {} of {} missing files", relinked.len(), missing);
+                for image_id in relinked {
+                    self.ctx.draw(image_id);
+                    self.watch_source(image_id);
+                }
+            }
+            Err(e) => eprintln!("{}: {}", folder.display(), e),
+        }
+    }
+
+    /// Lists the near-duplicates of an image and zooms to them.
+    fn find_similar(&mut self, image_id: uuid::Uuid) {
+        let library = self.ctx.renderer().library();
+        let mut found = similar::similar_to(library, &image_id, self.similarity);
+        if found.is_empty() {
+            println!("No similar images");
+            return;
+        }
+        println!("{} similar images", found.len());
+        for image in found.iter().filter_map(|id| library.get(id)) {
+            describe(image);
+        }
+        found.push(image_id);
+        self.ctx.zoom_to(&found);
+    }
+
+    /// Moves every set of near-duplicates to its own row under the board.
+    fn gather_similar(&mut self) {
+        let groups = similar::groups(self.ctx.renderer().library(), self.similarity);
+        if groups.is_empty() {
+            println!("No similar images");
+            return;
+        }
+        similar::gather(self.ctx.library_mut(), &groups);
+        for image_id in groups.iter().flatten() {
+            self.ctx.draw(*image_id);
+        }
+        println!("Gathered {} sets of similar images", groups.len());
+        self.ctx.zoom_to_fit();
+    }
+
+    /// Copies the linked files next to the board.
+    fn collect_assets(&mut self) {
+        let path = self.board_path.get_or_insert_with(|| DEFAULT_BOARD.into());
+        match relink::collect_assets(self.ctx.library_mut(), path) {
+            Ok(copied) => {
+                println!("Copied {} files next to {}", copied, path.display());
+                self.watch_board();
+            }
+            Err(e) => eprintln!("Cannot collect assets: {}", e),
+        }
+    }
+
+    /// Starts watching `folder` and remembers it in the board.
+    fn watch_folder(&mut self, folder: &Path) -> Result<(), String> {
+        let watcher = self.watcher.as_mut().ok_or("watch folders are disabled")?;
+        watcher.watch_folder(folder).map_err(|e| e.to_string())?;
+
+        let folder = folder.canonicalize().map_err(|e| e.to_string())?;
+        let watch_folders = &mut self.ctx.library_mut().watch_folders;
+        if !watch_folders.contains(&folder) {
+            watch_folders.push(folder);
+        }
+        Ok(())
+    }
+
+    /// Watches the folders of the board and the files its images come from.
+    fn watch_board(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        let library = self.ctx.renderer().library();
+        for folder in &library.watch_folders {
+            if let Err(e) = watcher.watch_folder(folder) {
+                eprintln!("{}: {}", folder.display(), e);
+            }
+        }
+        for (_, image) in library.iter() {
+            if let Some(source) = &image.source {
+                if watcher.watch_file(&source.path).is_ok() {
+                    // Catches edits made while the board was closed.
+                    watcher.changed(source.path.clone());
+                }
+            }
+        }
+    }
+
+    /// Reloads the image when its source file changes.
+    fn watch_source(&mut self, image_id: uuid::Uuid) {
+        let source = self
+            .ctx
+            .renderer()
+            .library()
+            .get(&image_id)
+            .and_then(|image| image.source.as_ref());
+        if let (Some(watcher), Some(source)) = (&mut self.watcher, source) {
+            if let Err(e) = watcher.watch_file(&source.path) {
+                log::warn!("{}: {}", source.path.display(), e);
+            }
+        }
+    }
+
+    /// Swaps the pixels of the images imported from `path` when the file
+    /// changed since they were decoded, or adds it if it is new in a watch
+    /// folder.
+    fn reload_or_import(&mut self, path: PathBuf) {
+        let library = self.ctx.renderer().library();
+        let linked = library.find_by_source(&path);
+        let modified = import::modified(&path);
+        let stale: Vec<uuid::Uuid> = linked
+            .iter()
+            .copied()
+            .filter(|image_id| {
+                library.get(image_id).is_some_and(|image| {
+                    // A missing file that shows up again is reloaded whatever its date.
+                    image.missing.is_some()
+                        || image
+                            .source
+                            .as_ref()
+                            .is_none_or(|source| source.modified != modified)
+                })
+            })
+            .collect();
+        if !linked.is_empty() && stale.is_empty() {
+            return;
+        }
+
+        let mut image = match Image::open(&path, [0., 0.]) {
+            Ok(image) => image,
+            // Most likely still being written, the next change retries.
+            Err(e) => {
+                log::debug!("{}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        if !linked.is_empty() {
+            for image_id in stale {
+                self.ctx.replace_image(image_id, &image);
+            }
+            return;
+        }
+
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if watcher.in_watch_folder(&path) {
+            image.position = watcher.place(self.ctx.renderer().library(), image.size());
+            self.add_image(image);
+        }
+    }
+
+    #[cfg(unix)]
+    fn handle_request(&mut self, request: ipc::Request) {
+        let result = match &request.command {
+            ipc::Command::AddImageFromPath(path) => {
+                self.open_image(path).map(|_| ()).map_err(|e| e.to_string())
+            }
+            ipc::Command::AddImageFromBytes(bytes) => {
+                let position = self.ctx.renderer().library().next_position();
+                Image::new(position, bytes.clone())
+                    .map(|image| {
+                        self.add_image(image);
+                    })
+                    .map_err(|e| e.to_string())
+            }
+            ipc::Command::Focus => {
+                self.ctx.window().set_minimized(false);
+                self.ctx.window().focus_window();
+                Ok(())
+            }
+            ipc::Command::LoadBoard(path) => board::load(path)
+                .map(|library| self.replace_board(library, path.clone()))
+                .map_err(|e| e.to_string()),
+            ipc::Command::WatchFolder(folder) => self.watch_folder(folder),
+        };
+        request.reply(result);
+    }
+}
+
+fn describe(image: &Image) {
+    let [width, height] = image.size();
+    let storage = image.storage_label();
+    match &image.source {
+        Some(source) => println!("{}x{} {} {}", width, height, storage, source.path.display()),
+        None => println!("{}x{} {}", width, height, storage),
+    }
+}
+
+/// Prints everything known about an image.
+fn inspect(image: &Image) {
+    describe(image);
+    for (label, value) in image.metadata.fields() {
+        println!("  {}: {}", label, value);
+    }
+    if !image.tags.is_empty() {
+        println!("  tags: {}", image.tags.join(", "));
+    }
+    if !image.note.is_empty() {
+        println!("  note: {}", image.note);
+    }
+    if !image.adjustments.is_identity() {
+        println!("  adjustments: {}", image.adjustments);
+    }
+}
+
+/// Hidden images in reading order, as numbered in the outline of Shift+H.
+fn hidden_outline(library: &Library) -> Vec<uuid::Uuid> {
+    let mut hidden: Vec<_> = library.iter().filter(|(_, image)| image.hidden).collect();
+    hidden.sort_by(|(_, a), (_, b)| {
+        a.position[1]
+            .total_cmp(&b.position[1])
+            .then(a.position[0].total_cmp(&b.position[0]))
     });
+    hidden.into_iter().map(|(key, _)| *key).collect()
+}
+
+/// `name` made safe to use as a file name.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
 }
@@ -0,0 +1,119 @@
+//! One line of text typed into the window. There are no widgets, the text is
+//! shown in the window title until Enter or Escape.
+
+use crate::reference::metadata::Provenance;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PromptKind {
+    Search,
+    /// Comma separated tags of the selected images.
+    Tags,
+    Note,
+    /// Source URL, author and license of the selected images, typed as
+    /// `url | author | license`. An empty field clears it.
+    Provenance,
+    /// Content of a text item, a new one at `position` when `text_id` is
+    /// None. `\n` starts a new line.
+    Text {
+        text_id: Option<uuid::Uuid>,
+        position: [f32; 2],
+    },
+    FrameName(uuid::Uuid),
+    /// Name of a color chip, written above its hex. Empty leaves it out.
+    ChipName(uuid::Uuid),
+    /// Number of a hidden image in the outline printed when it opens, to
+    /// show it again.
+    Reveal,
+    /// Adjustments of the selected images, applied as they are typed.
+    Adjust,
+    /// File the palettes of the selected images, or of all of them, are
+    /// written to next to the board, in the format its extension names.
+    PaletteFile,
+    /// Number or name of a color vision to simulate, from the menu printed
+    /// when it opens.
+    Vision,
+}
+
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+    /// The key that opened the prompt also arrives as a character, which
+    /// must not end up in the text.
+    skip_character: bool,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, text: String) -> Self {
+        Self {
+            kind,
+            text,
+            skip_character: true,
+        }
+    }
+
+    pub fn type_character(&mut self, character: char) {
+        if std::mem::take(&mut self.skip_character) || character.is_control() {
+            return;
+        }
+        self.text.push(character);
+    }
+
+    pub fn backspace(&mut self) {
+        self.skip_character = false;
+        self.text.pop();
+    }
+
+    /// Window title while the prompt is open, `status` is appended to it.
+    pub fn title(&self, status: &str) -> String {
+        let label = match self.kind {
+            PromptKind::Search => "Search",
+            PromptKind::Tags => "Tags",
+            PromptKind::Note => "Note",
+            PromptKind::Provenance => "Source | author | license",
+            PromptKind::Text { .. } => "Text",
+            PromptKind::FrameName(_) => "Frame name",
+            PromptKind::ChipName(_) => "Chip name",
+            PromptKind::Reveal => "Show hidden image",
+            PromptKind::Adjust => "Adjust",
+            PromptKind::Vision => "Simulate vision",
+            PromptKind::PaletteFile => "Export palette",
+        };
+        format!("RustyRef - {}: {}_ {}", label, self.text, status)
+    }
+}
+
+/// Provenance typed as `url | author | license`, fields left out at the end
+/// are empty.
+pub fn parse_provenance(text: &str) -> Provenance {
+    let mut fields = text
+        .split('|')
+        .map(str::trim)
+        .map(|field| Some(field.to_string()).filter(|field| !field.is_empty()));
+    Provenance {
+        source_url: fields.next().flatten(),
+        author: fields.next().flatten(),
+        license: fields.next().flatten(),
+    }
+}
+
+/// Provenance as `parse_provenance` reads it.
+pub fn format_provenance(provenance: &Provenance) -> String {
+    [
+        &provenance.source_url,
+        &provenance.author,
+        &provenance.license,
+    ]
+    .map(|field| field.as_deref().unwrap_or_default())
+    .join(" | ")
+}
+
+/// Tags typed as `a, b, c`.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|known| known == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
@@ -0,0 +1,101 @@
+//! Annotation tools and the eyedropper, picked with a key and used by
+//! dragging or clicking on the board.
+
+use crate::reference::annotation::Shape;
+
+/// Outline colors `C` cycles through.
+pub const PALETTE: [[u8; 4]; 5] = [
+    [230, 57, 70, 255],
+    [255, 204, 0, 255],
+    [46, 204, 113, 255],
+    [52, 152, 219, 255],
+    [245, 245, 245, 255],
+];
+/// Outline width on screen, whatever the zoom when drawing.
+pub const STROKE_PIXELS: f32 = 4.;
+/// Alpha of the fill of shapes drawn with Shift held.
+pub const FILL_ALPHA: u8 = 64;
+/// Screen distance the pointer moves before a freehand stroke gets a point.
+pub const FREEHAND_STEP_PIXELS: f32 = 3.;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Arrow,
+    Rectangle,
+    Ellipse,
+    Freehand,
+    /// Drags out a frame holding what lies in it.
+    Frame,
+    /// Picks the color of the pixel clicked.
+    Eyedropper,
+}
+
+impl Tool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Arrow => "Arrow",
+            Tool::Rectangle => "Rectangle",
+            Tool::Ellipse => "Ellipse",
+            Tool::Freehand => "Freehand",
+            Tool::Frame => "Frame",
+            Tool::Eyedropper => "Eyedropper",
+        }
+    }
+
+    /// Shape starting at `point`, grown by dragging. None for the frame tool
+    /// and the eyedropper.
+    pub fn start(self, point: [f32; 2]) -> Option<Shape> {
+        let shape = match self {
+            Tool::Arrow => Shape::Arrow {
+                from: point,
+                to: point,
+            },
+            Tool::Rectangle => Shape::Rectangle {
+                from: point,
+                to: point,
+            },
+            Tool::Ellipse => Shape::Ellipse {
+                from: point,
+                to: point,
+            },
+            Tool::Freehand => Shape::Freehand {
+                points: vec![point],
+            },
+            Tool::Frame | Tool::Eyedropper => return None,
+        };
+        Some(shape)
+    }
+}
+
+/// What dragging with the left button does until it is released.
+pub enum Drag {
+    /// Draws out an annotation.
+    Annotation {
+        annotation_id: uuid::Uuid,
+        /// Position its points are relative to, in the coordinates of the
+        /// image it is on.
+        origin: [f32; 2],
+        /// Last point added, in the annotation coordinates.
+        last: [f32; 2],
+    },
+    /// Draws out a frame from the corner `start`.
+    NewFrame {
+        frame_id: uuid::Uuid,
+        start: [f32; 2],
+    },
+    /// Moves a frame held by its title, from the board point `last`.
+    MoveFrame {
+        frame_id: uuid::Uuid,
+        last: [f32; 2],
+    },
+    /// Moves a group held anywhere on its contents.
+    MoveGroup {
+        group_id: uuid::Uuid,
+        last: [f32; 2],
+    },
+    /// Moves the `edges` of the crop of an image, see `crop::HANDLES`.
+    Crop {
+        image_id: uuid::Uuid,
+        edges: [bool; 4],
+    },
+}
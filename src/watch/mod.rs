@@ -0,0 +1,160 @@
+//! File-system watching for folders whose images are imported automatically
+//! and for the files board images were imported from.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use notify::{event::EventKind, RecursiveMode, Watcher as _};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    reference::{import, layout::Grid, Library},
+    ui::UserEvent,
+};
+
+/// Images imported from watch folders per grid row.
+const GRID_COLUMNS: usize = 6;
+
+/// Files are usually written in several chunks, wait for them to settle
+/// before decoding.
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+pub struct Watcher {
+    watcher: notify::RecommendedWatcher,
+    /// Everything handed to notify. Files are watched through their parent
+    /// directory so editors replacing them on save do not drop the watch.
+    directories: HashSet<PathBuf>,
+    folders: Vec<PathBuf>,
+    files: HashSet<PathBuf>,
+    pending: HashMap<PathBuf, Instant>,
+    grid: Option<Grid>,
+}
+
+impl Watcher {
+    /// Changed files are reported to the event loop as `UserEvent::FileChanged`.
+    pub fn new(proxy: EventLoopProxy<UserEvent>) -> notify::Result<Self> {
+        let watcher =
+            notify::recommended_watcher(
+                move |result: notify::Result<notify::Event>| match result {
+                    Ok(event)
+                        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) =>
+                    {
+                        for path in event.paths {
+                            let _ = proxy.send_event(UserEvent::FileChanged(path));
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("watch error: {}", e),
+                },
+            )?;
+
+        Ok(Self {
+            watcher,
+            directories: HashSet::new(),
+            folders: Vec::new(),
+            files: HashSet::new(),
+            pending: HashMap::new(),
+            grid: None,
+        })
+    }
+
+    fn watch_directory(&mut self, directory: &Path) -> notify::Result<()> {
+        if !self.directories.contains(directory) {
+            self.watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            self.directories.insert(directory.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Starts importing the images written to `folder`.
+    pub fn watch_folder(&mut self, folder: &Path) -> notify::Result<()> {
+        let folder = folder.canonicalize()?;
+        if self.folders.contains(&folder) {
+            return Ok(());
+        }
+
+        self.watch_directory(&folder)?;
+        self.folders.push(folder);
+        Ok(())
+    }
+
+    /// Starts reporting changes of the file at `path`, an image source.
+    pub fn watch_file(&mut self, path: &Path) -> notify::Result<()> {
+        if let Some(directory) = path.parent() {
+            self.watch_directory(directory)?;
+        }
+        self.files.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Stops following the image files of the board being replaced, and
+    /// lays the next watched imports out in a new grid. Watch folders stay.
+    pub fn forget_files(&mut self) {
+        self.files.clear();
+        self.grid = None;
+        let pending: Vec<PathBuf> = self.pending.keys().cloned().collect();
+        for path in pending {
+            if !self.in_watch_folder(&path) {
+                self.pending.remove(&path);
+            }
+        }
+        let unused: Vec<PathBuf> = self
+            .directories
+            .iter()
+            .filter(|directory| !self.folders.contains(directory))
+            .cloned()
+            .collect();
+        for directory in unused {
+            if let Err(e) = self.watcher.unwatch(&directory) {
+                log::warn!("{}: {}", directory.display(), e);
+            }
+            self.directories.remove(&directory);
+        }
+    }
+
+    /// Records a change reported by the watcher thread.
+    pub fn changed(&mut self, path: PathBuf) {
+        if self.files.contains(&path) || self.in_watch_folder(&path) {
+            self.pending.insert(path, Instant::now() + SETTLE_DELAY);
+        }
+    }
+
+    /// Whether `path` is an image new files of which must be imported.
+    pub fn in_watch_folder(&self, path: &Path) -> bool {
+        let watched = path
+            .parent()
+            .is_some_and(|parent| self.folders.iter().any(|folder| folder == parent));
+        watched && import::is_image_path(path)
+    }
+
+    /// When the next pending file settles, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    /// Files that stopped changing and can be imported.
+    pub fn take_settled(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
+
+    /// Next free cell of the grid watched images are laid out in, started
+    /// under the board the first time it is needed.
+    pub fn place(&mut self, library: &Library, size: [f32; 2]) -> [f32; 2] {
+        self.grid
+            .get_or_insert_with(|| Grid::below(library, GRID_COLUMNS))
+            .place(size)
+    }
+}